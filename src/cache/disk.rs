@@ -0,0 +1,524 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::web;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::cache::entry::{CacheEntry, CacheTier};
+
+/// Bumped whenever the table layout or the encoding of stored values
+/// changes. A file written with another version is wiped on open rather than
+/// misread.
+const SCHEMA_VERSION: i64 = 3;
+
+/// Rows deleted, or pages freed, per statement while compacting, so that the
+/// connection is never held for long.
+const COMPACTION_BATCH: i64 = 500;
+
+/// Cache kept in a SQLite file, so that species payloads and paid-for
+/// translations survive restarts. Entries live in namespaces, which carry
+/// their own ttl and size limit, and are JSON encoded. SQLite blocks, so
+/// namespaces run every call on the blocking thread pool.
+pub struct DiskCache {
+    store: Mutex<Store>,
+}
+
+struct Store {
+    connection: Connection,
+    /// Entries per namespace, counted once when the namespace is opened and
+    /// kept up to date since, so that writes need no `COUNT(*)`.
+    counts: HashMap<String, usize>,
+}
+
+impl DiskCache {
+    pub fn open(path: &Path) -> Result<Self, DiskCacheError> {
+        let connection = Connection::open(path)?;
+        let version: i64 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version != SCHEMA_VERSION {
+            // Incremental vacuum only takes effect once the file is rebuilt.
+            connection.execute_batch(
+                "DROP TABLE IF EXISTS entries;
+                 PRAGMA auto_vacuum = INCREMENTAL;
+                 VACUUM;",
+            )?;
+        }
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS entries (
+                namespace TEXT NOT NULL,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                stored_at INTEGER NOT NULL,
+                expires_at INTEGER NOT NULL,
+                PRIMARY KEY (namespace, key)
+            );
+            CREATE INDEX IF NOT EXISTS entries_stored_at ON entries (namespace, stored_at);",
+        )?;
+        connection.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        Ok(Self {
+            store: Mutex::new(Store {
+                connection,
+                counts: HashMap::new(),
+            }),
+        })
+    }
+
+    /// A view of the entries of one namespace, all stored for `ttl`. Past
+    /// `max_entries` its oldest entries go first; other namespaces are not
+    /// affected.
+    pub fn namespace(
+        self: &Arc<Self>,
+        namespace: &'static str,
+        ttl: Duration,
+        max_entries: usize,
+    ) -> DiskNamespace {
+        DiskNamespace {
+            cache: self.clone(),
+            namespace,
+            ttl: Some(ttl),
+            max_entries,
+        }
+    }
+
+    /// A view of the entries of one namespace, kept until overwritten or
+    /// removed: they neither expire nor get trimmed.
+    pub fn persistent_namespace(self: &Arc<Self>, namespace: &'static str) -> DiskNamespace {
+        DiskNamespace {
+            cache: self.clone(),
+            namespace,
+            ttl: None,
+            max_entries: usize::MAX,
+        }
+    }
+
+    /// The unexpired entry stored under `key`, if any. Entries that no longer
+    /// decode as `V` are dropped.
+    pub fn get<V: DeserializeOwned>(&self, namespace: &str, key: &str) -> Option<V> {
+        let mut store = self.store.lock().unwrap();
+        let value = store
+            .connection
+            .query_row(
+                "SELECT value FROM entries
+                 WHERE namespace = ?1 AND key = ?2 AND expires_at > ?3",
+                params![namespace, key, millis(SystemTime::now())],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .ok()??;
+        match serde_json::from_str(&value) {
+            Ok(value) => Some(value),
+            Err(_) => {
+                let _ = store.delete(namespace, key);
+                None
+            }
+        }
+    }
+
+    /// Stores `value` for `ttl`, then drops the oldest entries of the
+    /// namespace past `max_entries`. Like `get`, this is best effort: a cache
+    /// that cannot be written must not fail the request being served.
+    pub fn insert<V: Serialize>(
+        &self,
+        namespace: &str,
+        key: &str,
+        value: &V,
+        ttl: Duration,
+        max_entries: usize,
+    ) {
+        if max_entries == 0 {
+            return;
+        }
+        let value = match serde_json::to_string(value) {
+            Ok(value) => value,
+            Err(_) => return,
+        };
+        let mut store = self.store.lock().unwrap();
+        let expires_at = millis(SystemTime::now() + ttl);
+        if store.upsert(namespace, key, &value, expires_at).is_ok() {
+            let _ = store.trim(namespace, max_entries);
+        }
+    }
+
+    /// Stores `value` with no expiry and without trimming the namespace.
+    pub fn insert_persistent<V: Serialize>(&self, namespace: &str, key: &str, value: &V) {
+        let value = match serde_json::to_string(value) {
+            Ok(value) => value,
+            Err(_) => return,
+        };
+        let mut store = self.store.lock().unwrap();
+        let _ = store.upsert(namespace, key, &value, i64::MAX);
+    }
+
+    /// Unexpired entries of `namespace`, sorted by key.
+    pub fn entries(&self, namespace: &str) -> Result<Vec<CacheEntry>, DiskCacheError> {
+        let now = millis(SystemTime::now());
+        let store = self.store.lock().unwrap();
+        let mut statement = store.connection.prepare(
+            "SELECT key, stored_at, expires_at FROM entries
+             WHERE namespace = ?1 AND expires_at > ?2 ORDER BY key",
+        )?;
+        let rows = statement.query_map(params![namespace, now], |row| {
+            let stored_at: i64 = row.get(1)?;
+            let expires_at: i64 = row.get(2)?;
+            Ok(CacheEntry::new(
+                row.get(0)?,
+                CacheTier::Disk,
+                duration(now - stored_at),
+                duration(expires_at - stored_at),
+            ))
+        })?;
+        Ok(rows.collect::<Result<Vec<CacheEntry>, rusqlite::Error>>()?)
+    }
+
+    /// Removes the entries of `namespace` whose key matches, returning how
+    /// many there were.
+    pub fn remove_matching(
+        &self,
+        namespace: &str,
+        matches: impl Fn(&str) -> bool,
+    ) -> Result<usize, DiskCacheError> {
+        let mut store = self.store.lock().unwrap();
+        let keys = store
+            .connection
+            .prepare("SELECT key FROM entries WHERE namespace = ?1")?
+            .query_map(params![namespace], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<String>, rusqlite::Error>>()?;
+        let transaction = store.connection.transaction()?;
+        let mut removed = 0;
+        for key in keys.iter().filter(|key| matches(key)) {
+            removed += transaction.execute(
+                "DELETE FROM entries WHERE namespace = ?1 AND key = ?2",
+                params![namespace, key],
+            )?;
+        }
+        transaction.commit()?;
+        store.forget(namespace, removed);
+        Ok(removed)
+    }
+
+    /// Removes expired entries and gives the freed pages back to the file
+    /// system, a batch at a time so that other calls get the connection in
+    /// between. Returns how many entries were removed.
+    pub fn compact(&self) -> Result<usize, DiskCacheError> {
+        let mut removed = 0;
+        loop {
+            let mut store = self.store.lock().unwrap();
+            let expired = store.connection.execute(
+                "DELETE FROM entries WHERE rowid IN
+                 (SELECT rowid FROM entries WHERE expires_at <= ?1 LIMIT ?2)",
+                params![millis(SystemTime::now()), COMPACTION_BATCH],
+            )?;
+            if expired > 0 {
+                // Which namespaces they were in is not known, so recount.
+                store.counts.clear();
+            }
+            removed += expired;
+            if (expired as i64) < COMPACTION_BATCH {
+                break;
+            }
+        }
+        loop {
+            let store = self.store.lock().unwrap();
+            let free_pages: i64 =
+                store
+                    .connection
+                    .pragma_query_value(None, "freelist_count", |row| row.get(0))?;
+            if free_pages == 0 {
+                break;
+            }
+            store
+                .connection
+                .execute_batch(&format!("PRAGMA incremental_vacuum({});", COMPACTION_BATCH))?;
+        }
+        Ok(removed)
+    }
+}
+
+impl Store {
+    fn count(&mut self, namespace: &str) -> Result<usize, rusqlite::Error> {
+        if let Some(count) = self.counts.get(namespace) {
+            return Ok(*count);
+        }
+        let count: i64 = self.connection.query_row(
+            "SELECT COUNT(*) FROM entries WHERE namespace = ?1",
+            params![namespace],
+            |row| row.get(0),
+        )?;
+        self.counts.insert(String::from(namespace), count as usize);
+        Ok(count as usize)
+    }
+
+    fn upsert(
+        &mut self,
+        namespace: &str,
+        key: &str,
+        value: &str,
+        expires_at: i64,
+    ) -> Result<(), rusqlite::Error> {
+        let count = self.count(namespace)?;
+        let now = millis(SystemTime::now());
+        let updated = self.connection.execute(
+            "UPDATE entries SET value = ?3, stored_at = ?4, expires_at = ?5
+             WHERE namespace = ?1 AND key = ?2",
+            params![namespace, key, value, now, expires_at],
+        )?;
+        if updated == 0 {
+            self.connection.execute(
+                "INSERT INTO entries (namespace, key, value, stored_at, expires_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![namespace, key, value, now, expires_at],
+            )?;
+            self.counts.insert(String::from(namespace), count + 1);
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, namespace: &str, key: &str) -> Result<(), rusqlite::Error> {
+        let deleted = self.connection.execute(
+            "DELETE FROM entries WHERE namespace = ?1 AND key = ?2",
+            params![namespace, key],
+        )?;
+        self.forget(namespace, deleted);
+        Ok(())
+    }
+
+    /// Drops the oldest entries of `namespace` past `max_entries`.
+    fn trim(&mut self, namespace: &str, max_entries: usize) -> Result<usize, rusqlite::Error> {
+        let count = self.count(namespace)?;
+        if count <= max_entries {
+            return Ok(0);
+        }
+        let trimmed = self.connection.execute(
+            "DELETE FROM entries WHERE rowid IN
+             (SELECT rowid FROM entries WHERE namespace = ?1 ORDER BY stored_at LIMIT ?2)",
+            params![namespace, (count - max_entries) as i64],
+        )?;
+        self.forget(namespace, trimmed);
+        Ok(trimmed)
+    }
+
+    fn forget(&mut self, namespace: &str, removed: usize) {
+        if let Some(count) = self.counts.get_mut(namespace) {
+            *count = count.saturating_sub(removed);
+        }
+    }
+}
+
+pub struct DiskNamespace {
+    cache: Arc<DiskCache>,
+    namespace: &'static str,
+    /// `None` for persistent namespaces.
+    ttl: Option<Duration>,
+    max_entries: usize,
+}
+
+impl DiskNamespace {
+    pub async fn get<V: DeserializeOwned + Send + 'static>(&self, key: &str) -> Option<V> {
+        let (cache, namespace, key) = (self.cache.clone(), self.namespace, String::from(key));
+        web::block(move || cache.get(namespace, &key))
+            .await
+            .ok()
+            .flatten()
+    }
+
+    pub async fn insert<V: Serialize + Send + 'static>(&self, key: &str, value: V) {
+        let (cache, namespace, key) = (self.cache.clone(), self.namespace, String::from(key));
+        let (ttl, max_entries) = (self.ttl, self.max_entries);
+        let _ = web::block(move || match ttl {
+            Some(ttl) => cache.insert(namespace, &key, &value, ttl, max_entries),
+            None => cache.insert_persistent(namespace, &key, &value),
+        })
+        .await;
+    }
+
+    pub async fn entries(&self) -> Result<Vec<CacheEntry>, DiskCacheError> {
+        let (cache, namespace) = (self.cache.clone(), self.namespace);
+        web::block(move || cache.entries(namespace))
+            .await
+            .map_err(|_| DiskCacheError::Unavailable)?
+    }
+
+    pub async fn remove_matching(
+        &self,
+        matches: impl Fn(&str) -> bool + Send + 'static,
+    ) -> Result<usize, DiskCacheError> {
+        let (cache, namespace) = (self.cache.clone(), self.namespace);
+        web::block(move || cache.remove_matching(namespace, matches))
+            .await
+            .map_err(|_| DiskCacheError::Unavailable)?
+    }
+}
+
+fn duration(millis: i64) -> Duration {
+    Duration::from_millis(millis.max(0) as u64)
+}
+
+fn millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as i64)
+}
+
+#[derive(Debug)]
+pub enum DiskCacheError {
+    Storage(rusqlite::Error),
+    /// The blocking thread pool could not run the call.
+    Unavailable,
+}
+
+impl fmt::Display for DiskCacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiskCacheError::Storage(error) => write!(f, "disk cache storage error: {}", error),
+            DiskCacheError::Unavailable => write!(f, "disk cache unavailable"),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for DiskCacheError {
+    fn from(error: rusqlite::Error) -> Self {
+        DiskCacheError::Storage(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf, thread, time::Duration};
+
+    use claim::assert_ok;
+    use rusqlite::Connection;
+
+    use crate::cache::disk::DiskCache;
+
+    const LIMIT: usize = 10;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!(
+            "yap-{}-{}-{}.sqlite",
+            name,
+            std::process::id(),
+            rand::random::<u32>()
+        ))
+    }
+
+    #[test]
+    fn get_returns_values_surviving_a_reopen_until_they_expire() {
+        let path = temp_path("reopen");
+        let cache = DiskCache::open(&path).unwrap();
+        cache.insert("species", "pikachu", &25, Duration::from_secs(60), LIMIT);
+        cache.insert("species", "raichu", &26, Duration::from_millis(20), LIMIT);
+        drop(cache);
+
+        thread::sleep(Duration::from_millis(30));
+        let cache = DiskCache::open(&path).unwrap();
+        let pikachu = cache.get::<u32>("species", "pikachu");
+        let raichu = cache.get::<u32>("species", "raichu");
+        let other_namespace = cache.get::<u32>("translation", "pikachu");
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(pikachu, Some(25));
+        assert_eq!(raichu, None);
+        assert!(other_namespace.is_none());
+    }
+
+    #[test]
+    fn insert_drops_the_oldest_entries_of_the_namespace_past_its_size_limit() {
+        let path = temp_path("trim");
+        let cache = DiskCache::open(&path).unwrap();
+        cache.insert(
+            "translation",
+            "yoda:Hello",
+            &0,
+            Duration::from_secs(60),
+            LIMIT,
+        );
+        thread::sleep(Duration::from_millis(2));
+        cache.insert("species", "bulbasaur", &1, Duration::from_secs(60), 2);
+        thread::sleep(Duration::from_millis(2));
+        cache.insert("species", "ivysaur", &2, Duration::from_secs(60), 2);
+        thread::sleep(Duration::from_millis(2));
+        cache.insert("species", "ivysaur", &2, Duration::from_secs(60), 2);
+        cache.insert("species", "venusaur", &3, Duration::from_secs(60), 2);
+
+        let bulbasaur = cache.get::<u32>("species", "bulbasaur");
+        let ivysaur = cache.get::<u32>("species", "ivysaur");
+        let venusaur = cache.get::<u32>("species", "venusaur");
+        let translation = cache.get::<u32>("translation", "yoda:Hello");
+        assert_ok!(cache.compact());
+        fs::remove_file(&path).unwrap();
+
+        assert!(bulbasaur.is_none());
+        assert_eq!(ivysaur, Some(2));
+        assert_eq!(venusaur, Some(3));
+        assert_eq!(translation, Some(0));
+    }
+
+    #[test]
+    fn persistent_entries_survive_compaction() {
+        let path = temp_path("persistent");
+        let cache = DiskCache::open(&path).unwrap();
+        cache.insert_persistent("jobs", "pretranslation", &1);
+        cache.insert("species", "pikachu", &25, Duration::from_millis(1), LIMIT);
+        thread::sleep(Duration::from_millis(5));
+
+        assert_ok!(cache.compact());
+        let checkpoint = cache.get::<u32>("jobs", "pretranslation");
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(checkpoint, Some(1));
+    }
+
+    #[test]
+    fn remove_matching_only_touches_the_namespace() {
+        let path = temp_path("remove");
+        let cache = DiskCache::open(&path).unwrap();
+        cache.insert(
+            "translation",
+            "yoda:Hello",
+            &1,
+            Duration::from_secs(60),
+            LIMIT,
+        );
+        cache.insert(
+            "translation",
+            "pirate:Hello",
+            &2,
+            Duration::from_secs(60),
+            LIMIT,
+        );
+        cache.insert("species", "yoda:Hello", &3, Duration::from_secs(60), LIMIT);
+
+        let removed = cache.remove_matching("translation", |key| key.starts_with("yoda:"));
+        let translations = cache.entries("translation").unwrap();
+        let species = cache.entries("species").unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(removed.unwrap(), 1);
+        assert_eq!(translations.len(), 1);
+        assert_eq!(translations[0].key, "pirate:Hello");
+        assert_eq!(translations[0].ttl_secs, 60);
+        assert_eq!(species.len(), 1);
+    }
+
+    #[test]
+    fn open_discards_files_written_with_another_schema_version() {
+        let path = temp_path("schema");
+        let cache = DiskCache::open(&path).unwrap();
+        cache.insert("species", "pikachu", &25, Duration::from_secs(60), LIMIT);
+        drop(cache);
+        Connection::open(&path)
+            .unwrap()
+            .pragma_update(None, "user_version", 0)
+            .unwrap();
+
+        let cache = DiskCache::open(&path).unwrap();
+        let pikachu = cache.get::<u32>("species", "pikachu");
+        fs::remove_file(&path).unwrap();
+
+        assert!(pikachu.is_none());
+    }
+}
@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheTier {
+    Memory,
+    Disk,
+}
+
+/// What the admin api reports about a cached entry.
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct CacheEntry {
+    pub key: String,
+    pub tier: CacheTier,
+    pub age_secs: u64,
+    pub ttl_secs: u64,
+}
+
+impl CacheEntry {
+    pub fn new(key: String, tier: CacheTier, age: Duration, ttl: Duration) -> Self {
+        Self {
+            key,
+            tier,
+            age_secs: age.as_secs(),
+            ttl_secs: ttl.as_secs(),
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::cache::entry::{CacheEntry, CacheTier};
+
+struct Entry<V> {
+    value: V,
+    inserted_at: Instant,
+}
+
+/// In-memory cache whose entries expire `ttl` after insertion. Once
+/// `max_entries` is reached the oldest entry makes room for the new one.
+/// Expired entries are kept for a further grace period, during which they
+/// can still be read with their age.
+pub struct TtlCache<V> {
+    ttl: Duration,
+    grace_period: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<String, Entry<V>>>,
+}
+
+impl<V: Clone> TtlCache<V> {
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            ttl,
+            grace_period: Duration::ZERO,
+            max_entries,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_grace_period(mut self, grace_period: Duration) -> Self {
+        self.grace_period = grace_period;
+        self
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// The entry stored under `key` and how long ago it was inserted, even
+    /// if expired, as long as it is within the grace period.
+    pub fn get_with_age(&self, key: &str) -> Option<(V, Duration)> {
+        let mut entries = self.entries.lock().unwrap();
+        let age = entries.get(key)?.inserted_at.elapsed();
+        if age < self.ttl + self.grace_period {
+            entries.get(key).map(|entry| (entry.value.clone(), age))
+        } else {
+            entries.remove(key);
+            None
+        }
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// Removes the entries whose key matches, returning how many there were.
+    pub fn remove_matching(&self, matches: impl Fn(&str) -> bool) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        entries.retain(|key, _| !matches(key));
+        before - entries.len()
+    }
+
+    /// Keys of the entries still readable, with their age.
+    pub fn entries(&self) -> Vec<CacheEntry> {
+        let retention = self.ttl + self.grace_period;
+        let mut entries = self
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, entry)| (key.clone(), entry.inserted_at.elapsed()))
+            .filter(|(_, age)| *age < retention)
+            .map(|(key, age)| CacheEntry::new(key, CacheTier::Memory, age, self.ttl))
+            .collect::<Vec<CacheEntry>>();
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+        entries
+    }
+
+    pub fn insert(&self, key: String, value: V) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            let retention = self.ttl + self.grace_period;
+            entries.retain(|_, entry| entry.inserted_at.elapsed() < retention);
+            if entries.len() >= self.max_entries {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.inserted_at)
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+        entries.insert(
+            key,
+            Entry {
+                value,
+                inserted_at: Instant::now(),
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use crate::cache::memory::TtlCache;
+
+    fn get(cache: &TtlCache<i32>, key: &str) -> Option<i32> {
+        cache
+            .get_with_age(key)
+            .filter(|(_, age)| *age < cache.ttl())
+            .map(|(value, _)| value)
+    }
+
+    #[test]
+    fn get_returns_values_until_they_expire() {
+        let cache = TtlCache::new(Duration::from_millis(50), 10);
+        cache.insert(String::from("pikachu"), 25);
+
+        assert_eq!(get(&cache, "pikachu"), Some(25));
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(get(&cache, "pikachu"), None);
+    }
+
+    #[test]
+    fn get_with_age_returns_expired_values_during_the_grace_period() {
+        let cache =
+            TtlCache::new(Duration::from_millis(20), 10).with_grace_period(Duration::from_secs(60));
+        cache.insert(String::from("pikachu"), 25);
+
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(get(&cache, "pikachu"), None);
+        let (value, age) = cache.get_with_age("pikachu").unwrap();
+        assert_eq!(value, 25);
+        assert!(age >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn insert_evicts_the_oldest_entry_when_full() {
+        let cache = TtlCache::new(Duration::from_secs(60), 2);
+        cache.insert(String::from("bulbasaur"), 1);
+        thread::sleep(Duration::from_millis(2));
+        cache.insert(String::from("ivysaur"), 2);
+        cache.insert(String::from("venusaur"), 3);
+
+        assert_eq!(get(&cache, "bulbasaur"), None);
+        assert_eq!(get(&cache, "ivysaur"), Some(2));
+        assert_eq!(get(&cache, "venusaur"), Some(3));
+    }
+}
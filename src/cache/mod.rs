@@ -0,0 +1,4 @@
+pub mod disk;
+pub mod entry;
+pub mod memory;
+pub mod stale;
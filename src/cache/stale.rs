@@ -0,0 +1,89 @@
+use std::{collections::HashSet, sync::Mutex, time::Duration};
+
+/// How long past its ttl an entry may still be served: right away while it
+/// is refreshed in the background, or when refreshing it fails.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StalePolicy {
+    pub while_revalidate: Duration,
+    pub if_error: Duration,
+}
+
+impl StalePolicy {
+    /// How long expired entries have to be kept around.
+    pub fn grace_period(&self) -> Duration {
+        self.while_revalidate.max(self.if_error)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Staleness {
+    Fresh,
+    /// Expired, being refreshed in the background.
+    Stale,
+    /// Expired, and the refresh just failed.
+    RevalidationFailed,
+}
+
+impl Staleness {
+    /// Value of the `Warning` header marking a stale response, as defined by
+    /// RFC 7234.
+    pub fn warning(&self) -> Option<&'static str> {
+        match self {
+            Staleness::Fresh => None,
+            Staleness::Stale => Some("110 - \"Response is Stale\""),
+            Staleness::RevalidationFailed => Some("111 - \"Revalidation Failed\""),
+        }
+    }
+
+    /// The staler of the two, for responses built from several entries.
+    pub fn max(self, other: Staleness) -> Staleness {
+        if other > self {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+/// Keys with a background refresh in flight, so that a burst of requests
+/// for an expired entry only triggers one.
+#[derive(Default)]
+pub struct Refreshes {
+    keys: Mutex<HashSet<String>>,
+}
+
+impl Refreshes {
+    /// Whether the caller should start a refresh of `key`; if so it must
+    /// call `finish` once done.
+    pub fn start(&self, key: &str) -> bool {
+        self.keys.lock().unwrap().insert(String::from(key))
+    }
+
+    pub fn finish(&self, key: &str) {
+        self.keys.lock().unwrap().remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::stale::{Refreshes, Staleness};
+
+    #[test]
+    fn refreshes_only_start_once_per_key() {
+        let refreshes = Refreshes::default();
+
+        assert!(refreshes.start("pikachu"));
+        assert!(!refreshes.start("pikachu"));
+        refreshes.finish("pikachu");
+        assert!(refreshes.start("pikachu"));
+    }
+
+    #[test]
+    fn max_keeps_the_staler_value() {
+        assert_eq!(
+            Staleness::Fresh.max(Staleness::RevalidationFailed),
+            Staleness::RevalidationFailed
+        );
+        assert_eq!(Staleness::Stale.max(Staleness::Fresh), Staleness::Stale);
+    }
+}
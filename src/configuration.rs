@@ -0,0 +1,424 @@
+use std::{
+    collections::HashMap,
+    env,
+    fmt::{self, Debug},
+    fs,
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
+
+use crate::funtranslations_api::client::DEFAULT_POST_THRESHOLD;
+use crate::problem::ErrorFormat;
+
+/// A credential. `Debug` prints a placeholder, so that settings can be
+/// logged without leaking it.
+#[derive(Clone)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: String) -> Self {
+        Secret(value)
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret([REDACTED])")
+    }
+}
+
+/// Runtime settings, read from `YAP_*` environment variables with defaults
+/// suitable for running against the public upstream APIs.
+pub struct Settings {
+    pub bind_address: String,
+    /// Bearer token guarding the `/admin` routes, which are disabled when
+    /// no token is configured.
+    pub admin_token: Option<Secret>,
+    pub poke_api_url: String,
+    pub funtranslations_api_url: String,
+    /// Subscription key; when set, the paid tier quotas apply.
+    pub funtranslations_api_secret: Option<Secret>,
+    pub funtranslations_hourly_quota: u32,
+    pub funtranslations_daily_quota: u32,
+    /// Texts longer than this many bytes are POSTed; 0 POSTs every text.
+    pub funtranslations_post_threshold: usize,
+    pub upstream_timeout: Duration,
+    pub species_index_ttl: Duration,
+    /// How long a species payload is used before revalidating it upstream.
+    pub species_ttl: Duration,
+    /// How long a name PokeAPI does not know is answered 404 without asking
+    /// again.
+    pub not_found_ttl: Duration,
+    pub not_found_max_entries: usize,
+    /// Species fetched at most to answer one listing filtered on the
+    /// legendary flag.
+    pub legendary_scan_limit: usize,
+    /// `Cache-Control` max-age of plain Pokémon info. Translated info uses
+    /// `translation_cache_ttl` instead.
+    pub pokemon_info_max_age: Duration,
+    /// Mixed with the current date to pick the Pokémon of the day; replicas
+    /// sharing a seed agree on the pick.
+    pub daily_seed: u64,
+    pub translation_max_text_length: usize,
+    pub translation_cache_ttl: Duration,
+    pub translation_cache_max_entries: usize,
+    /// SQLite file backing the in-memory caches; persistence is off when
+    /// unset.
+    pub disk_cache_path: Option<PathBuf>,
+    /// Size limits of the disk cache, per namespace, so that churn among
+    /// species never evicts paid-for translations.
+    pub disk_cache_species_max_entries: usize,
+    pub disk_cache_translation_max_entries: usize,
+    pub disk_cache_species_ttl: Duration,
+    pub disk_cache_translation_ttl: Duration,
+    pub disk_cache_compaction_interval: Duration,
+    /// Whether to warm the caches at startup, before reporting ready.
+    pub warmup_enabled: bool,
+    /// Species to warm; the whole first generation when empty.
+    pub warmup_species: Vec<String>,
+    /// Calls of the preferred provider's hourly and daily budgets that the
+    /// warmup leaves to live traffic.
+    pub warmup_quota_reserve: u32,
+    /// Whether to translate every description in the background, within
+    /// the preferred provider's quota.
+    pub pretranslation_enabled: bool,
+    pub pretranslation_batch_size: usize,
+    /// Pause between two pre-translation batches.
+    pub pretranslation_interval: Duration,
+    /// Calls of the preferred provider's hourly and daily budgets that
+    /// pre-translation leaves to live traffic.
+    pub pretranslation_quota_reserve: u32,
+    /// Error format served to clients not asking for one in `Accept`; legacy
+    /// until clients have moved to problem details.
+    pub error_format: ErrorFormat,
+    /// Prefix of the problem type URIs.
+    pub problem_type_base: String,
+    /// Whether translations failing for lack of quota are queued, and
+    /// answered with `202 Accepted`, rather than failed.
+    pub async_jobs_enabled: bool,
+    /// Where finished translation jobs are posted.
+    pub job_webhook_url: Option<String>,
+    /// Pause before retrying a queued translation.
+    pub job_retry_interval: Duration,
+    /// Attempts after which a queued translation is failed.
+    pub job_max_attempts: u32,
+    /// How long finished jobs can be polled.
+    pub job_retention: Duration,
+    pub max_jobs: usize,
+    /// Queued translations past which new ones are refused with 429.
+    pub max_pending_jobs: usize,
+    /// How long past their ttl species and translations are served while
+    /// refreshed in the background.
+    pub stale_while_revalidate: Duration,
+    /// How long past their ttl species and translations are served when
+    /// refreshing them fails.
+    pub stale_if_error: Duration,
+    /// Dialect to provider overrides, e.g. `pirate=libretranslate`.
+    pub translation_routes: HashMap<String, String>,
+    pub libretranslate_api_url: Option<String>,
+    pub libretranslate_languages: Vec<String>,
+    /// Providers tried in order after the one routed to a dialect fails.
+    pub translation_chain: Vec<String>,
+    pub translation_failure_threshold: u32,
+    pub translation_cooldown: Duration,
+    /// Per provider request budgets, e.g. `funtranslations=5/3600` for five
+    /// requests per hour.
+    pub translation_quotas: HashMap<String, (u32, Duration)>,
+}
+
+impl Settings {
+    pub fn from_env() -> Self {
+        Self::from_vars(&|key| env::var(key).ok())
+    }
+
+    /// Settings read from `vars` rather than the process environment, with
+    /// the same defaults.
+    pub fn from_vars(vars: &dyn Fn(&str) -> Option<String>) -> Self {
+        let funtranslations_api_secret = secret_from_env(vars, "YAP_FUNTRANSLATIONS_API_SECRET");
+        let (funtranslations_hourly_quota, funtranslations_daily_quota) =
+            if funtranslations_api_secret.is_some() {
+                (
+                    env_or(vars, "YAP_FUNTRANSLATIONS_PAID_HOURLY_QUOTA", 1_000),
+                    env_or(vars, "YAP_FUNTRANSLATIONS_PAID_DAILY_QUOTA", 20_000),
+                )
+            } else {
+                (
+                    env_or(vars, "YAP_FUNTRANSLATIONS_HOURLY_QUOTA", 5),
+                    env_or(vars, "YAP_FUNTRANSLATIONS_DAILY_QUOTA", 60),
+                )
+            };
+        Self {
+            bind_address: env_or(vars, "YAP_BIND_ADDRESS", String::from("127.0.0.1:8080")),
+            admin_token: secret_from_env(vars, "YAP_ADMIN_TOKEN"),
+            poke_api_url: env_or(
+                vars,
+                "YAP_POKE_API_URL",
+                String::from("https://pokeapi.co/api/v2"),
+            ),
+            funtranslations_api_url: env_or(
+                vars,
+                "YAP_FUNTRANSLATIONS_API_URL",
+                String::from("https://api.funtranslations.com"),
+            ),
+            funtranslations_api_secret,
+            funtranslations_hourly_quota,
+            funtranslations_daily_quota,
+            funtranslations_post_threshold: env_or(
+                vars,
+                "YAP_FUNTRANSLATIONS_POST_THRESHOLD",
+                DEFAULT_POST_THRESHOLD,
+            ),
+            upstream_timeout: Duration::from_secs(env_or(vars, "YAP_UPSTREAM_TIMEOUT_SECS", 10)),
+            species_index_ttl: Duration::from_secs(env_or(
+                vars,
+                "YAP_SPECIES_INDEX_TTL_SECS",
+                24 * 60 * 60,
+            )),
+            species_ttl: Duration::from_secs(env_or(vars, "YAP_SPECIES_TTL_SECS", 24 * 60 * 60)),
+            not_found_ttl: Duration::from_secs(env_or(vars, "YAP_NOT_FOUND_TTL_SECS", 5 * 60)),
+            not_found_max_entries: env_or(vars, "YAP_NOT_FOUND_MAX_ENTRIES", 10_000),
+            legendary_scan_limit: env_or(vars, "YAP_LEGENDARY_SCAN_LIMIT", 200),
+            pokemon_info_max_age: Duration::from_secs(env_or(
+                vars,
+                "YAP_POKEMON_INFO_MAX_AGE_SECS",
+                7 * 24 * 60 * 60,
+            )),
+            daily_seed: env_or(vars, "YAP_DAILY_SEED", 0),
+            translation_max_text_length: env_or(vars, "YAP_TRANSLATION_MAX_TEXT_LENGTH", 1000),
+            translation_cache_ttl: Duration::from_secs(env_or(
+                vars,
+                "YAP_TRANSLATION_CACHE_TTL_SECS",
+                24 * 60 * 60,
+            )),
+            translation_cache_max_entries: env_or(
+                vars,
+                "YAP_TRANSLATION_CACHE_MAX_ENTRIES",
+                10_000,
+            ),
+            disk_cache_path: vars("YAP_DISK_CACHE_PATH").map(PathBuf::from),
+            disk_cache_species_max_entries: env_or(
+                vars,
+                "YAP_DISK_CACHE_SPECIES_MAX_ENTRIES",
+                5_000,
+            ),
+            disk_cache_translation_max_entries: env_or(
+                vars,
+                "YAP_DISK_CACHE_TRANSLATION_MAX_ENTRIES",
+                100_000,
+            ),
+            disk_cache_species_ttl: Duration::from_secs(env_or(
+                vars,
+                "YAP_DISK_CACHE_SPECIES_TTL_SECS",
+                7 * 24 * 60 * 60,
+            )),
+            disk_cache_translation_ttl: Duration::from_secs(env_or(
+                vars,
+                "YAP_DISK_CACHE_TRANSLATION_TTL_SECS",
+                30 * 24 * 60 * 60,
+            )),
+            disk_cache_compaction_interval: Duration::from_secs(env_or(
+                vars,
+                "YAP_DISK_CACHE_COMPACTION_INTERVAL_SECS",
+                60 * 60,
+            )),
+            warmup_enabled: env_or(vars, "YAP_WARMUP_ENABLED", false),
+            warmup_species: env_list(vars, "YAP_WARMUP_SPECIES"),
+            warmup_quota_reserve: env_or(vars, "YAP_WARMUP_QUOTA_RESERVE", 2),
+            pretranslation_enabled: env_or(vars, "YAP_PRETRANSLATION_ENABLED", false),
+            pretranslation_batch_size: env_or(vars, "YAP_PRETRANSLATION_BATCH_SIZE", 5),
+            pretranslation_interval: Duration::from_secs(env_or(
+                vars,
+                "YAP_PRETRANSLATION_INTERVAL_SECS",
+                15 * 60,
+            )),
+            pretranslation_quota_reserve: env_or(vars, "YAP_PRETRANSLATION_QUOTA_RESERVE", 2),
+            error_format: env_or(vars, "YAP_ERROR_FORMAT", ErrorFormat::Legacy),
+            problem_type_base: vars("YAP_PROBLEM_TYPE_BASE")
+                .unwrap_or_else(|| String::from("/problems/")),
+            async_jobs_enabled: env_or(vars, "YAP_ASYNC_JOBS_ENABLED", false),
+            job_webhook_url: vars("YAP_JOB_WEBHOOK_URL"),
+            job_retry_interval: Duration::from_secs(env_or(
+                vars,
+                "YAP_JOB_RETRY_INTERVAL_SECS",
+                300,
+            )),
+            job_max_attempts: env_or(vars, "YAP_JOB_MAX_ATTEMPTS", 12),
+            job_retention: Duration::from_secs(env_or(
+                vars,
+                "YAP_JOB_RETENTION_SECS",
+                24 * 60 * 60,
+            )),
+            max_jobs: env_or(vars, "YAP_MAX_JOBS", 10000),
+            max_pending_jobs: env_or(vars, "YAP_MAX_PENDING_JOBS", 1000),
+            stale_while_revalidate: Duration::from_secs(env_or(
+                vars,
+                "YAP_STALE_WHILE_REVALIDATE_SECS",
+                60 * 60,
+            )),
+            stale_if_error: Duration::from_secs(env_or(
+                vars,
+                "YAP_STALE_IF_ERROR_SECS",
+                24 * 60 * 60,
+            )),
+            translation_routes: env_list(vars, "YAP_TRANSLATION_ROUTES")
+                .into_iter()
+                .map(|route| match route.split_once('=') {
+                    Some((dialect, provider)) => {
+                        (String::from(dialect.trim()), String::from(provider.trim()))
+                    }
+                    None => panic!("invalid value for YAP_TRANSLATION_ROUTES: {}", route),
+                })
+                .collect(),
+            libretranslate_api_url: vars("YAP_LIBRETRANSLATE_API_URL"),
+            libretranslate_languages: env_list(vars, "YAP_LIBRETRANSLATE_LANGUAGES"),
+            translation_chain: env_list_or(
+                vars,
+                "YAP_TRANSLATION_CHAIN",
+                &["funtranslations", "libretranslate", "local"],
+            ),
+            translation_failure_threshold: env_or(vars, "YAP_TRANSLATION_FAILURE_THRESHOLD", 3),
+            translation_cooldown: Duration::from_secs(env_or(
+                vars,
+                "YAP_TRANSLATION_COOLDOWN_SECS",
+                30,
+            )),
+            translation_quotas: env_list(vars, "YAP_TRANSLATION_QUOTAS")
+                .into_iter()
+                .map(|quota| parse_quota(&quota))
+                .collect(),
+        }
+    }
+}
+
+fn env_or<T>(vars: &dyn Fn(&str) -> Option<String>, key: &str, default: T) -> T
+where
+    T: FromStr,
+    T::Err: Debug,
+{
+    match vars(key) {
+        Some(value) => value
+            .parse()
+            .unwrap_or_else(|error| panic!("invalid value for {}: {:?}", key, error)),
+        None => default,
+    }
+}
+
+/// Reads a secret from `key`, or from the file named by `<key>_FILE`, as
+/// mounted by most secret stores. Surrounding whitespace is ignored.
+fn secret_from_env(vars: &dyn Fn(&str) -> Option<String>, key: &str) -> Option<Secret> {
+    let value = match vars(key) {
+        Some(value) => value,
+        None => {
+            let file_key = format!("{}_FILE", key);
+            let path = vars(&file_key)?;
+            fs::read_to_string(&path)
+                .unwrap_or_else(|error| panic!("cannot read {} ({}): {}", file_key, path, error))
+        }
+    };
+    let value = value.trim();
+    if value.is_empty() {
+        None
+    } else {
+        Some(Secret::new(String::from(value)))
+    }
+}
+
+/// Comma separated values, empty when the variable is not set.
+fn env_list(vars: &dyn Fn(&str) -> Option<String>, key: &str) -> Vec<String> {
+    vars(key)
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn env_list_or(vars: &dyn Fn(&str) -> Option<String>, key: &str, default: &[&str]) -> Vec<String> {
+    match vars(key) {
+        Some(_) => env_list(vars, key),
+        None => default.iter().map(|item| String::from(*item)).collect(),
+    }
+}
+
+/// Parses `provider=limit/seconds`.
+fn parse_quota(quota: &str) -> (String, (u32, Duration)) {
+    let parsed = quota.split_once('=').and_then(|(provider, budget)| {
+        let (limit, seconds) = budget.split_once('/')?;
+        Some((
+            String::from(provider.trim()),
+            (
+                limit.trim().parse().ok()?,
+                Duration::from_secs(seconds.trim().parse().ok()?),
+            ),
+        ))
+    });
+    parsed.unwrap_or_else(|| panic!("invalid value for YAP_TRANSLATION_QUOTAS: {}", quota))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, env, fs};
+
+    use crate::configuration::{secret_from_env, Secret, Settings};
+
+    fn vars(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars = pairs
+            .iter()
+            .map(|(key, value)| (String::from(*key), String::from(*value)))
+            .collect::<HashMap<String, String>>();
+        move |key| vars.get(key).cloned()
+    }
+
+    #[test]
+    fn secret_is_redacted_when_debug_printed() {
+        let secret = Secret::new(String::from("hunter2"));
+
+        assert_eq!(format!("{:?}", secret), "Secret([REDACTED])");
+        assert_eq!(secret.expose(), "hunter2");
+    }
+
+    #[test]
+    fn secret_from_env_reads_the_variable_first_then_the_file() {
+        let path = env::temp_dir().join(format!("yap-secret-{}", std::process::id()));
+        fs::write(&path, "  from-file\n").unwrap();
+        let path = path.to_str().unwrap();
+
+        let from_file = secret_from_env(&vars(&[("YAP_SECRET_FILE", path)]), "YAP_SECRET");
+        let from_variable = secret_from_env(
+            &vars(&[("YAP_SECRET", " from-variable "), ("YAP_SECRET_FILE", path)]),
+            "YAP_SECRET",
+        );
+        let missing = secret_from_env(&vars(&[]), "YAP_SECRET");
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(from_file.unwrap().expose(), "from-file");
+        assert_eq!(from_variable.unwrap().expose(), "from-variable");
+        assert!(missing.is_none());
+    }
+
+    #[test]
+    fn secret_from_env_ignores_blank_values() {
+        let secret = secret_from_env(&vars(&[("YAP_SECRET", "  \n")]), "YAP_SECRET");
+
+        assert!(secret.is_none());
+    }
+
+    #[test]
+    fn from_vars_applies_the_paid_quotas_with_a_secret() {
+        let free = Settings::from_vars(&vars(&[]));
+        let paid = Settings::from_vars(&vars(&[("YAP_FUNTRANSLATIONS_API_SECRET", "s3cr3t")]));
+
+        assert_eq!(free.funtranslations_hourly_quota, 5);
+        assert_eq!(paid.funtranslations_hourly_quota, 1_000);
+        assert!(free.admin_token.is_none());
+    }
+}
@@ -0,0 +1,155 @@
+use actix_web::{http, http::header, HttpResponse, HttpResponseBuilder, ResponseError};
+use serde::Serialize;
+use std::fmt::{self, Display};
+use utoipa::ToSchema;
+
+use crate::poke_api::client::PokeApiClientError;
+use crate::translation::translator::TranslationError;
+
+/// Upstream names reported with the errors they cause.
+pub const POKE_API: &str = "pokeapi";
+pub const TRANSLATION: &str = "translation";
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PokeError {
+    #[serde(skip_serializing)]
+    pub status_code: u16,
+    /// Stable `PE_*` identifier of the error, e.g. `PE_NOT_FOUND`.
+    pub code: String,
+    pub message: String,
+    /// Upstream service whose failure caused the error, if any.
+    #[serde(skip_serializing)]
+    pub upstream: Option<&'static str>,
+}
+
+impl PokeError {
+    pub fn bad_request(message: String) -> Self {
+        PokeError {
+            status_code: http::StatusCode::BAD_REQUEST.as_u16(),
+            code: String::from("PE_BAD_REQUEST"),
+            message,
+            upstream: None,
+        }
+    }
+
+    pub fn not_found(message: String) -> Self {
+        PokeError {
+            status_code: http::StatusCode::NOT_FOUND.as_u16(),
+            code: String::from("PE_NOT_FOUND"),
+            message,
+            upstream: None,
+        }
+    }
+
+    pub fn unauthorized() -> Self {
+        PokeError {
+            status_code: http::StatusCode::UNAUTHORIZED.as_u16(),
+            code: String::from("PE_UNAUTHORIZED"),
+            message: String::from("missing or invalid admin token"),
+            upstream: None,
+        }
+    }
+
+    pub fn internal() -> Self {
+        PokeError {
+            status_code: http::StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            code: String::from("PE_INTERNAL"),
+            message: String::from("internal error"),
+            upstream: None,
+        }
+    }
+
+    /// Too many translation jobs are pending to accept another.
+    pub fn queue_full() -> Self {
+        PokeError {
+            status_code: http::StatusCode::TOO_MANY_REQUESTS.as_u16(),
+            code: String::from("PE_QUEUE_FULL"),
+            message: String::from("too many pending translation jobs"),
+            upstream: None,
+        }
+    }
+
+    pub fn forbidden(message: String) -> Self {
+        PokeError {
+            status_code: http::StatusCode::FORBIDDEN.as_u16(),
+            code: String::from("PE_FORBIDDEN"),
+            message,
+            upstream: None,
+        }
+    }
+}
+
+impl Display for PokeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl ResponseError for PokeError {
+    fn status_code(&self) -> http::StatusCode {
+        http::StatusCode::from_u16(self.status_code).unwrap()
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponseBuilder::new(self.status_code())
+            .insert_header(header::ContentType(mime::APPLICATION_JSON))
+            .body(serde_json::to_string(&self).unwrap())
+    }
+}
+
+impl From<PokeApiClientError> for PokeError {
+    fn from(error: PokeApiClientError) -> Self {
+        match error {
+            PokeApiClientError::BadRequest { message } => PokeError::bad_request(message),
+            PokeApiClientError::InternalError => PokeError {
+                status_code: http::StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                code: String::from("PE_INTERNAL"),
+                message: String::from("internal error"),
+                upstream: Some(POKE_API),
+            },
+            PokeApiClientError::NotFound => PokeError::not_found(String::from("pokemon not found")),
+        }
+    }
+}
+
+impl From<TranslationError> for PokeError {
+    fn from(error: TranslationError) -> Self {
+        match error {
+            TranslationError::BadRequest { message } => PokeError::bad_request(message),
+            TranslationError::UnsupportedDialect { dialect } => {
+                PokeError::bad_request(format!("unsupported dialect '{}'", dialect))
+            }
+            TranslationError::NotFound => PokeError::not_found(String::from("not found")),
+            TranslationError::RateLimited => PokeError {
+                status_code: http::StatusCode::TOO_MANY_REQUESTS.as_u16(),
+                code: String::from("PE_RATE_LIMITED"),
+                message: String::from("translation rate limit exceeded"),
+                upstream: Some(TRANSLATION),
+            },
+            TranslationError::QuotaExhausted => PokeError {
+                status_code: http::StatusCode::TOO_MANY_REQUESTS.as_u16(),
+                code: String::from("PE_QUOTA_EXHAUSTED"),
+                message: String::from("translation quota exhausted"),
+                upstream: Some(TRANSLATION),
+            },
+            TranslationError::Timeout => PokeError {
+                status_code: http::StatusCode::GATEWAY_TIMEOUT.as_u16(),
+                code: String::from("PE_TIMEOUT"),
+                message: String::from("translation provider timed out"),
+                upstream: Some(TRANSLATION),
+            },
+            TranslationError::Unavailable => PokeError {
+                status_code: http::StatusCode::SERVICE_UNAVAILABLE.as_u16(),
+                code: String::from("PE_UNAVAILABLE"),
+                message: String::from("no translation provider available"),
+                upstream: Some(TRANSLATION),
+            },
+            TranslationError::InternalError => PokeError {
+                status_code: http::StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                code: String::from("PE_INTERNAL"),
+                message: String::from("internal error"),
+                upstream: Some(TRANSLATION),
+            },
+        }
+    }
+}
@@ -0,0 +1,460 @@
+use std::rc::Rc;
+
+use actix_web::{
+    body::AnyBody,
+    dev::{RequestHead, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{self, HeaderValue},
+    web, Error,
+};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::error::PokeError;
+
+const TEXT_HTML: &str = "text/html";
+
+/// Media types a response body can be served as. Handlers always produce
+/// JSON, which `ContentNegotiation` converts to the requested format.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Yaml,
+    Csv,
+    MessagePack,
+    Text,
+}
+
+#[derive(Deserialize)]
+struct FormatParams {
+    format: Option<String>,
+}
+
+impl Format {
+    /// Value of the `?format=` override.
+    fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "json" => Some(Format::Json),
+            "yaml" => Some(Format::Yaml),
+            "csv" => Some(Format::Csv),
+            "msgpack" => Some(Format::MessagePack),
+            "text" => Some(Format::Text),
+            _ => None,
+        }
+    }
+
+    fn from_media_type(media_type: &str) -> Option<Format> {
+        match media_type {
+            "*/*" | "application/*" | "application/json" | "application/problem+json" => {
+                Some(Format::Json)
+            }
+            "application/yaml" | "application/x-yaml" | "text/yaml" => Some(Format::Yaml),
+            "text/csv" => Some(Format::Csv),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(Format::MessagePack)
+            }
+            "text/plain" => Some(Format::Text),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Yaml => "yaml",
+            Format::Csv => "csv",
+            Format::MessagePack => "msgpack",
+            Format::Text => "text",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::Yaml => "application/yaml",
+            Format::Csv => "text/csv; charset=utf-8",
+            Format::MessagePack => "application/msgpack",
+            Format::Text => "text/plain; charset=utf-8",
+        }
+    }
+
+    /// The format named by `?format=`, or else the supported media type the
+    /// `Accept` header prefers, JSON when it names none.
+    pub fn requested(request: &RequestHead) -> Result<Format, PokeError> {
+        if let Some(name) = format_param(request) {
+            return Format::from_name(&name).ok_or_else(|| {
+                PokeError::bad_request(format!(
+                    "unknown format '{}', expected json, yaml, csv, msgpack or text",
+                    name
+                ))
+            });
+        }
+        let accept = request
+            .headers
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("");
+        Ok(Format::negotiate(accept))
+    }
+
+    fn negotiate(accept: &str) -> Format {
+        preferred_media_types(accept)
+            .into_iter()
+            .find_map(Format::from_media_type)
+            .unwrap_or(Format::Json)
+    }
+
+    /// Converts a JSON body to this format.
+    pub fn encode(&self, json: &[u8]) -> Option<Vec<u8>> {
+        let value = serde_json::from_slice::<Value>(json).ok()?;
+        match self {
+            Format::Json => Some(json.to_vec()),
+            Format::Yaml => serde_yaml::to_string(&value).ok().map(String::into_bytes),
+            Format::Csv => to_csv(&value),
+            Format::MessagePack => rmp_serde::to_vec_named(&value).ok(),
+            Format::Text => Some(to_text(&value).into_bytes()),
+        }
+    }
+}
+
+/// Whether the client would rather have an HTML page than any of the
+/// formats, which `?format=` always forces.
+pub fn wants_html(request: &RequestHead) -> bool {
+    if format_param(request).is_some() {
+        return false;
+    }
+    let accept = request
+        .headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("");
+    preferred_media_types(accept)
+        .into_iter()
+        .find(|media_type| {
+            *media_type == TEXT_HTML || Format::from_media_type(media_type).is_some()
+        })
+        == Some(TEXT_HTML)
+}
+
+fn format_param(request: &RequestHead) -> Option<String> {
+    let query_string = request.uri.query().unwrap_or("");
+    web::Query::<FormatParams>::from_query(query_string)
+        .ok()
+        .and_then(|params| params.into_inner().format)
+}
+
+/// Media types named by an `Accept` header, most preferred first.
+fn preferred_media_types(accept: &str) -> Vec<&str> {
+    let mut media_types = accept
+        .split(',')
+        .map(|media_type| {
+            let mut parameters = media_type.split(';');
+            let name = parameters.next().unwrap_or("").trim();
+            let quality = parameters
+                .filter_map(|parameter| parameter.trim().strip_prefix("q="))
+                .find_map(|quality| quality.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (name, quality)
+        })
+        .filter(|(_, quality)| *quality > 0.0)
+        .collect::<Vec<(&str, f32)>>();
+    // Stable, so equally preferred types keep the client's order.
+    media_types.sort_by(|a, b| b.1.total_cmp(&a.1));
+    media_types.into_iter().map(|(name, _)| name).collect()
+}
+
+/// Items of a body: the results of a page, the elements of an array, or
+/// else the body itself.
+fn rows(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(items) => items.iter().collect(),
+        Value::Object(fields) => match fields.get("results") {
+            Some(Value::Array(results)) => results.iter().collect(),
+            _ => vec![value],
+        },
+        _ => vec![value],
+    }
+}
+
+/// One row per item, with a column per field in the order the model
+/// declares them; nested values are kept as JSON.
+fn to_csv(value: &Value) -> Option<Vec<u8>> {
+    let rows = rows(value);
+    let mut columns: Vec<&str> = Vec::new();
+    for row in &rows {
+        match row {
+            Value::Object(fields) => {
+                for name in fields.keys() {
+                    if !columns.contains(&name.as_str()) {
+                        columns.push(name);
+                    }
+                }
+            }
+            _ if !columns.contains(&"value") => columns.push("value"),
+            _ => {}
+        }
+    }
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    if !rows.is_empty() {
+        writer.write_record(&columns).ok()?;
+    }
+    for row in rows {
+        let record = columns.iter().map(|column| match row {
+            Value::Object(fields) => fields.get(*column).map(cell).unwrap_or_default(),
+            _ if *column == "value" => cell(row),
+            _ => String::new(),
+        });
+        writer.write_record(record).ok()?;
+    }
+    writer.into_inner().ok()
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// One line per item: `name: description` for species, the translated text
+/// for translations and `code: detail` for errors.
+fn to_text(value: &Value) -> String {
+    rows(value)
+        .into_iter()
+        .map(|row| match row {
+            Value::Object(fields) => text_line(fields),
+            other => cell(other),
+        })
+        .map(|line| line + "\n")
+        .collect()
+}
+
+fn text_line(fields: &Map<String, Value>) -> String {
+    let text = |name: &str| fields.get(name).and_then(Value::as_str);
+    if let (Some(name), Some(description)) = (text("name"), text("description")) {
+        format!("{}: {}", name, description)
+    } else if let Some(translated) = text("translated") {
+        String::from(translated)
+    } else if let (Some(code), Some(detail)) = (text("code"), text("detail").or(text("message"))) {
+        format!("{}: {}", code, detail)
+    } else {
+        fields
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, cell(value)))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+/// Middleware serving JSON bodies, error bodies included, in the format
+/// the client asked for.
+pub struct ContentNegotiation;
+
+impl<S> Transform<S, ServiceRequest> for ContentNegotiation
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<AnyBody>, Error = Error> + 'static,
+{
+    type Response = ServiceResponse<AnyBody>;
+    type Error = Error;
+    type Transform = ContentNegotiationMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ContentNegotiationMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct ContentNegotiationMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S> Service<ServiceRequest> for ContentNegotiationMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<AnyBody>, Error = Error> + 'static,
+{
+    type Response = ServiceResponse<AnyBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        // An unknown format is refused by `ProblemDetails`, so that the error
+        // is rendered like any other.
+        let format = Format::requested(request.head()).unwrap_or(Format::Json);
+        let service = self.service.clone();
+        Box::pin(async move {
+            let mut response = service.call(request).await?;
+            let is_json = response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|content_type| {
+                    content_type.starts_with("application/json")
+                        || content_type.starts_with("application/problem+json")
+                });
+            if !is_json {
+                return Ok(response);
+            }
+            response
+                .headers_mut()
+                .append(header::VARY, HeaderValue::from_static("Accept"));
+            if format == Format::Json {
+                return Ok(response);
+            }
+            Ok(response.map_body(|head, body| match body {
+                AnyBody::Bytes(json) => match format.encode(&json) {
+                    Some(encoded) => {
+                        head.headers_mut().insert(
+                            header::CONTENT_TYPE,
+                            HeaderValue::from_static(format.content_type()),
+                        );
+                        AnyBody::from(encoded)
+                    }
+                    None => AnyBody::Bytes(json),
+                },
+                body => body,
+            }))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{get, http::StatusCode, test, App, HttpResponse};
+    use serde_json::{json, Value};
+
+    use crate::error::PokeError;
+    use crate::format::{wants_html, ContentNegotiation, Format};
+    use crate::problem::{ErrorFormat, ProblemDetails};
+    use crate::routes::pokemon::PokemonInfoV1;
+
+    #[get("/pokemon")]
+    async fn list() -> HttpResponse {
+        HttpResponse::Ok().json(json!({
+            "next": null,
+            "results": [
+                {"name": "mewtwo", "description": "It was created by a scientist.", "habitat": "rare", "is_legendary": true},
+                {"name": "zubat", "description": "Forms colonies, \"in\" caves.", "habitat": "cave", "is_legendary": false},
+            ],
+        }))
+    }
+
+    #[get("/pokemon/missingno")]
+    async fn missing() -> Result<HttpResponse, PokeError> {
+        Err(PokeError::not_found(String::from("pokemon not found")))
+    }
+
+    #[test]
+    fn negotiate_picks_the_most_preferred_supported_media_type() {
+        assert_eq!(Format::negotiate(""), Format::Json);
+        assert_eq!(Format::negotiate("text/csv"), Format::Csv);
+        assert_eq!(
+            Format::negotiate("text/html, application/yaml;q=0.5, text/plain;q=0.9"),
+            Format::Text
+        );
+        assert_eq!(
+            Format::negotiate("application/msgpack;q=0, image/png"),
+            Format::Json
+        );
+    }
+
+    #[test]
+    fn csv_columns_follow_the_field_order_of_the_model() {
+        let pokemon_info = PokemonInfoV1 {
+            name: String::from("mewtwo"),
+            description: String::from("It was created by a scientist."),
+            habitat: String::from("rare"),
+            is_legendary: true,
+        };
+        let json = serde_json::to_vec(&pokemon_info).unwrap();
+
+        let csv = Format::Csv.encode(&json).unwrap();
+
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "name,description,habitat,is_legendary\n\
+             mewtwo,It was created by a scientist.,rare,true\n"
+        );
+    }
+
+    #[test]
+    fn wants_html_only_when_preferred_and_not_overridden() {
+        let wants = |uri: &str, accept: &str| {
+            let request = test::TestRequest::get()
+                .uri(uri)
+                .insert_header(("Accept", accept))
+                .to_http_request();
+            wants_html(request.head())
+        };
+
+        assert!(wants(
+            "/pokemon/mewtwo",
+            "text/html,application/xhtml+xml,*/*;q=0.8"
+        ));
+        assert!(!wants("/pokemon/mewtwo", "*/*"));
+        assert!(!wants("/pokemon/mewtwo", "application/json, text/html"));
+        assert!(!wants("/pokemon/mewtwo?format=json", "text/html"));
+    }
+
+    #[actix_rt::test]
+    async fn bodies_are_served_in_the_requested_format() {
+        // Arrange
+        let app = test::init_service(
+            App::new()
+                .wrap(ProblemDetails::new(ErrorFormat::Legacy, "/problems/"))
+                .wrap(ContentNegotiation)
+                .service(list)
+                .service(missing),
+        )
+        .await;
+        let get = |uri: &str, accept: &str| {
+            test::TestRequest::get()
+                .uri(uri)
+                .insert_header(("Accept", accept))
+                .to_request()
+        };
+
+        // Act
+        let csv = test::call_service(&app, get("/pokemon", "text/csv")).await;
+        let text = test::call_service(&app, get("/pokemon?format=text", "text/csv")).await;
+        let msgpack = test::call_service(&app, get("/pokemon", "application/msgpack")).await;
+        let yaml_error =
+            test::call_service(&app, get("/pokemon/missingno", "application/yaml")).await;
+        let unknown =
+            test::call_service(&app, get("/pokemon?format=xml", "application/problem+json")).await;
+
+        // Assert
+        assert_eq!(
+            csv.headers().get("content-type").unwrap(),
+            "text/csv; charset=utf-8"
+        );
+        assert_eq!(csv.headers().get("vary").unwrap(), "Accept");
+        assert_eq!(
+            test::read_body(csv).await,
+            "name,description,habitat,is_legendary\n\
+             mewtwo,It was created by a scientist.,rare,true\n\
+             zubat,\"Forms colonies, \"\"in\"\" caves.\",cave,false\n"
+        );
+        assert_eq!(
+            test::read_body(text).await,
+            "mewtwo: It was created by a scientist.\nzubat: Forms colonies, \"in\" caves.\n"
+        );
+        let decoded: Value = rmp_serde::from_slice(&test::read_body(msgpack).await).unwrap();
+        assert_eq!(decoded["results"][0]["name"], "mewtwo");
+        assert_eq!(yaml_error.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            test::read_body(yaml_error).await,
+            "code: PE_NOT_FOUND\nmessage: pokemon not found\n"
+        );
+        assert_eq!(unknown.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            unknown.headers().get("content-type").unwrap(),
+            "application/problem+json"
+        );
+    }
+}
@@ -1,19 +1,37 @@
 use std::time::Duration;
 
-use reqwest::{Client, StatusCode};
-use serde::Serialize;
+use async_trait::async_trait;
+use reqwest::{header::HeaderValue, Client, StatusCode};
 use serde_json::Value;
 
-#[derive(Serialize, Debug)]
-pub struct Translation {
-    pub dialect: String,
-    pub original: String,
-    pub translated: String,
-}
+use crate::configuration::Secret;
+use crate::funtranslations_api::quota::{QuotaLimits, QuotaTracker};
+use crate::translation::translator::{
+    Capabilities, QuotaStatus, Translation, TranslationError, Translator,
+};
+
+const API_SECRET_HEADER: &str = "X-Funtranslations-Api-Secret";
+
+/// Texts longer than this, in bytes, are sent as a form body rather than in
+/// the query string, away from URL length limits and proxy access logs.
+pub const DEFAULT_POST_THRESHOLD: usize = 256;
+
+/// The funtranslations endpoints this service routes dialects to by default.
+const SUPPORTED_DIALECTS: [&str; 6] = [
+    "yoda",
+    "shakespeare",
+    "pirate",
+    "minion",
+    "sith",
+    "valyrian",
+];
 
 pub struct FunTranslationsApiClient {
     http_client: Client,
     base_url: String,
+    quota: Option<QuotaTracker>,
+    api_secret: Option<HeaderValue>,
+    post_threshold: usize,
 }
 
 impl FunTranslationsApiClient {
@@ -22,27 +40,73 @@ impl FunTranslationsApiClient {
         Self {
             http_client,
             base_url,
+            quota: None,
+            api_secret: None,
+            post_threshold: DEFAULT_POST_THRESHOLD,
         }
     }
 
+    /// Sends texts longer than `post_threshold` bytes with POST; 0 means
+    /// always.
+    pub fn with_post_threshold(mut self, post_threshold: usize) -> Self {
+        self.post_threshold = post_threshold;
+        self
+    }
+
+    /// Authenticates every request with a paid subscription key. The header
+    /// is marked sensitive so that it is redacted from debug output.
+    pub fn with_api_secret(mut self, api_secret: &Secret) -> Self {
+        let mut value = HeaderValue::from_str(api_secret.expose())
+            .expect("the funtranslations api secret is not a valid header value");
+        value.set_sensitive(true);
+        self.api_secret = Some(value);
+        self
+    }
+
+    /// Tracks calls against `limits` and refuses to send requests once they
+    /// are spent.
+    pub fn with_quota(mut self, limits: QuotaLimits) -> Self {
+        self.quota = Some(QuotaTracker::new(limits));
+        self
+    }
+
+    pub fn quota_status(&self) -> Option<QuotaStatus> {
+        self.quota.as_ref().map(QuotaTracker::status)
+    }
+
     pub async fn translate(
         &self,
         dialect: &str,
         text: &str,
     ) -> Result<Translation, FunTranslationsApiClientError> {
+        validate_dialect(dialect)?;
+        if let Some(quota) = &self.quota {
+            if !quota.try_acquire() {
+                return Err(FunTranslationsApiClientError::QuotaExhausted);
+            }
+        }
         let url = format!("{}/translate/{}.json", self.base_url, dialect);
-        let response = self
-            .http_client
-            .get(url)
-            .query(&[("text", text)])
-            .send()
-            .await?;
+        let mut request = if text.len() > self.post_threshold {
+            self.http_client.post(url).form(&[("text", text)])
+        } else {
+            self.http_client.get(url).query(&[("text", text)])
+        };
+        if let Some(api_secret) = &self.api_secret {
+            request = request.header(API_SECRET_HEADER, api_secret.clone());
+        }
+        let response = request.send().await?;
         match response.status() {
             StatusCode::OK => {
                 let json = response.text().await?;
                 self.build_translation(&json)
             }
             StatusCode::NOT_FOUND => Err(FunTranslationsApiClientError::NotFound),
+            StatusCode::TOO_MANY_REQUESTS => {
+                if let Some(quota) = &self.quota {
+                    quota.exhaust();
+                }
+                Err(FunTranslationsApiClientError::TooManyRequests)
+            }
             _ => Err(FunTranslationsApiClientError::InternalError),
         }
     }
@@ -63,6 +127,7 @@ impl FunTranslationsApiClient {
                         dialect: String::from(translation),
                         original: String::from(text),
                         translated: String::from(translated),
+                        engine: String::from("funtranslations"),
                     })
                 } else {
                     Err(FunTranslationsApiClientError::InternalError)
@@ -76,15 +141,81 @@ impl FunTranslationsApiClient {
     }
 }
 
+#[async_trait]
+impl Translator for FunTranslationsApiClient {
+    fn name(&self) -> &str {
+        "funtranslations"
+    }
+
+    async fn translate(&self, dialect: &str, text: &str) -> Result<Translation, TranslationError> {
+        Ok(FunTranslationsApiClient::translate(self, dialect, text).await?)
+    }
+
+    fn supported_dialects(&self) -> Vec<String> {
+        SUPPORTED_DIALECTS
+            .iter()
+            .map(|d| String::from(*d))
+            .collect()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            remote: true,
+            rate_limited: true,
+            max_text_length: None,
+        }
+    }
+
+    fn quota(&self) -> Option<QuotaStatus> {
+        self.quota_status()
+    }
+}
+
+/// Dialects end up in the request path, so only plain names are allowed.
+fn validate_dialect(dialect: &str) -> Result<(), FunTranslationsApiClientError> {
+    if !dialect.is_empty()
+        && dialect
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        Ok(())
+    } else {
+        Err(FunTranslationsApiClientError::BadRequest {
+            message: format!("invalid dialect '{}'", dialect),
+        })
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum FunTranslationsApiClientError {
     InternalError,
     NotFound,
-    BadRequest { message: String },
+    TooManyRequests,
+    /// Our own budget is spent, so the request was not sent.
+    QuotaExhausted,
+    Timeout,
+    BadRequest {
+        message: String,
+    },
+}
+
+impl From<FunTranslationsApiClientError> for TranslationError {
+    fn from(error: FunTranslationsApiClientError) -> Self {
+        match error {
+            FunTranslationsApiClientError::InternalError => TranslationError::InternalError,
+            FunTranslationsApiClientError::NotFound => TranslationError::NotFound,
+            FunTranslationsApiClientError::TooManyRequests => TranslationError::RateLimited,
+            FunTranslationsApiClientError::QuotaExhausted => TranslationError::QuotaExhausted,
+            FunTranslationsApiClientError::Timeout => TranslationError::Timeout,
+            FunTranslationsApiClientError::BadRequest { message } => {
+                TranslationError::BadRequest { message }
+            }
+        }
+    }
 }
 
 impl From<serde_json::Error> for FunTranslationsApiClientError {
-    fn from(error: serde_json::Error) -> Self {
+    fn from(_error: serde_json::Error) -> Self {
         FunTranslationsApiClientError::InternalError
     }
 }
@@ -93,6 +224,8 @@ impl From<reqwest::Error> for FunTranslationsApiClientError {
     fn from(error: reqwest::Error) -> Self {
         if let Some(StatusCode::NOT_FOUND) = error.status() {
             FunTranslationsApiClientError::NotFound
+        } else if error.is_timeout() {
+            FunTranslationsApiClientError::Timeout
         } else {
             FunTranslationsApiClientError::InternalError
         }
@@ -107,12 +240,16 @@ mod tests {
     use fake::{Fake, Faker};
     use serde_json::json;
     use wiremock::{
-        matchers::{any, method, path},
-        Mock, MockServer, ResponseTemplate,
+        matchers::{any, body_string, header, method, path, query_param},
+        Mock, MockServer, Request, ResponseTemplate,
     };
 
-    use crate::funtranslations_api::client::{
-        FunTranslationsApiClient, FunTranslationsApiClientError,
+    use crate::{
+        configuration::Secret,
+        funtranslations_api::{
+            client::{FunTranslationsApiClient, FunTranslationsApiClientError},
+            quota::QuotaLimits,
+        },
     };
 
     #[tokio::test]
@@ -145,7 +282,7 @@ mod tests {
             .await;
 
         // Act
-        let translation = funtranslations_api_client.translate(&dialect, &text).await;
+        let translation = funtranslations_api_client.translate(&dialect, text).await;
 
         assert_ok!(&translation);
         let translation = translation.unwrap();
@@ -154,6 +291,132 @@ mod tests {
         assert_eq!(translation.translated, "Lost a planet,  master obiwan has.");
     }
 
+    #[tokio::test]
+    async fn translate_posts_texts_longer_than_the_threshold_as_a_form() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let funtranslations_api_client =
+            FunTranslationsApiClient::new(mock_server.uri(), Duration::from_millis(200))
+                .with_post_threshold(10);
+        let text = "Master Obiwan has lost a planet.";
+
+        let json_body = json!({
+            "success": {
+              "total": 1
+            },
+            "contents": {
+              "translated": "Lost a planet,  master obiwan has.",
+              "text": text,
+              "translation": "yoda"
+            }
+          }
+        );
+        Mock::given(path("/translate/yoda.json"))
+            .and(method("POST"))
+            .and(header("Content-Type", "application/x-www-form-urlencoded"))
+            .and(body_string(
+                serde_urlencoded::to_string([("text", text)]).unwrap(),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json_body))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let translation = funtranslations_api_client.translate("yoda", text).await;
+
+        assert_ok!(&translation);
+    }
+
+    #[tokio::test]
+    async fn translate_sends_short_texts_in_the_query_string() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let funtranslations_api_client =
+            FunTranslationsApiClient::new(mock_server.uri(), Duration::from_millis(200))
+                .with_post_threshold(10);
+
+        Mock::given(path("/translate/yoda.json"))
+            .and(method("GET"))
+            .and(query_param("text", "Hello"))
+            .and(body_string(""))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let translation = funtranslations_api_client.translate("yoda", "Hello").await;
+
+        assert_eq!(
+            translation.unwrap_err(),
+            FunTranslationsApiClientError::NotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn translate_sends_the_api_secret_when_configured() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let api_secret = Secret::new(Faker.fake::<String>());
+        let funtranslations_api_client =
+            FunTranslationsApiClient::new(mock_server.uri(), Duration::from_millis(200))
+                .with_api_secret(&api_secret);
+
+        let json_body = json!({
+            "success": {
+              "total": 1
+            },
+            "contents": {
+              "translated": "Lost a planet,  master obiwan has.",
+              "text": "Master Obiwan has lost a planet.",
+              "translation": "yoda"
+            }
+          }
+        );
+        Mock::given(path("/translate/yoda.json"))
+            .and(method("GET"))
+            .and(header("X-Funtranslations-Api-Secret", api_secret.expose()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json_body))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let translation = funtranslations_api_client
+            .translate("yoda", "Master Obiwan has lost a planet.")
+            .await;
+
+        assert_ok!(&translation);
+    }
+
+    #[tokio::test]
+    async fn translate_sends_no_api_secret_by_default() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let funtranslations_api_client =
+            FunTranslationsApiClient::new(mock_server.uri(), Duration::from_millis(200));
+
+        Mock::given(|request: &Request| {
+            !request
+                .headers
+                .keys()
+                .any(|name| name.as_str() == "x-funtranslations-api-secret")
+        })
+        .respond_with(ResponseTemplate::new(404))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+        // Act
+        let translation = funtranslations_api_client.translate("yoda", "Hello").await;
+
+        assert_eq!(
+            translation.unwrap_err(),
+            FunTranslationsApiClientError::NotFound
+        );
+    }
+
     #[tokio::test]
     async fn translate_fails_if_the_server_returns_404() {
         // Arrange
@@ -178,6 +441,78 @@ mod tests {
         assert_eq!(error, FunTranslationsApiClientError::NotFound);
     }
 
+    #[tokio::test]
+    async fn translate_fails_if_the_server_returns_429() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let funtranslations_api_client =
+            FunTranslationsApiClient::new(mock_server.uri(), Duration::from_millis(200));
+
+        let dialect = Faker.fake::<String>();
+        let text = Faker.fake::<String>();
+        Mock::given(path(format!("/translate/{}.json", &dialect)))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(429))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let translation = funtranslations_api_client.translate(&dialect, &text).await;
+
+        assert_err!(&translation);
+        let error = translation.unwrap_err();
+        assert_eq!(error, FunTranslationsApiClientError::TooManyRequests);
+    }
+
+    #[tokio::test]
+    async fn translate_stops_sending_requests_when_the_quota_is_exhausted() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let funtranslations_api_client =
+            FunTranslationsApiClient::new(mock_server.uri(), Duration::from_millis(200))
+                .with_quota(QuotaLimits {
+                    hourly: 1,
+                    daily: 60,
+                });
+
+        let json_body = json!({
+            "success": {
+              "total": 1
+            },
+            "contents": {
+              "translated": "Lost a planet,  master obiwan has.",
+              "text": "Master Obiwan has lost a planet.",
+              "translation": "yoda"
+            }
+          }
+        );
+        Mock::given(path("/translate/yoda.json"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json_body))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let first = funtranslations_api_client
+            .translate("yoda", "Master Obiwan has lost a planet.")
+            .await;
+        let second = funtranslations_api_client
+            .translate("yoda", "Master Obiwan has lost a planet.")
+            .await;
+
+        assert_ok!(&first);
+        assert_err!(&second);
+        assert_eq!(
+            second.unwrap_err(),
+            FunTranslationsApiClientError::QuotaExhausted
+        );
+        let status = funtranslations_api_client.quota_status().unwrap();
+        assert_eq!(status.hourly_remaining, 0);
+        assert_eq!(status.daily_remaining, 59);
+    }
+
     #[tokio::test]
     async fn translate_fails_if_the_server_returns_500() {
         // Arrange
@@ -0,0 +1,141 @@
+use std::{
+    sync::Mutex,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::translation::translator::QuotaStatus;
+
+const SECONDS_PER_HOUR: f64 = 60.0 * 60.0;
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy)]
+pub struct QuotaLimits {
+    pub hourly: u32,
+    pub daily: u32,
+}
+
+struct QuotaState {
+    tokens: f64,
+    last_refill: Instant,
+    day: u64,
+    used_today: u32,
+}
+
+/// Our own view of the funtranslations budget: a token bucket refilling
+/// `hourly` tokens per hour, plus a counter reset at midnight UTC. Calls are
+/// refused once either runs out, before the API starts answering 429.
+pub struct QuotaTracker {
+    limits: QuotaLimits,
+    state: Mutex<QuotaState>,
+}
+
+impl QuotaTracker {
+    pub fn new(limits: QuotaLimits) -> Self {
+        Self {
+            limits,
+            state: Mutex::new(QuotaState {
+                tokens: f64::from(limits.hourly),
+                last_refill: Instant::now(),
+                day: today(),
+                used_today: 0,
+            }),
+        }
+    }
+
+    /// Takes one call from the budget, if there is any left.
+    pub fn try_acquire(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state);
+        if state.tokens < 1.0 || state.used_today >= self.limits.daily {
+            return false;
+        }
+        state.tokens -= 1.0;
+        state.used_today += 1;
+        true
+    }
+
+    /// Empties the hourly bucket, for when the API says we are over budget
+    /// even though we did not think so.
+    pub fn exhaust(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.tokens = 0.0;
+        state.last_refill = Instant::now();
+    }
+
+    pub fn status(&self) -> QuotaStatus {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state);
+        QuotaStatus {
+            hourly_limit: self.limits.hourly,
+            hourly_remaining: state.tokens.floor() as u32,
+            daily_limit: self.limits.daily,
+            daily_remaining: self.limits.daily.saturating_sub(state.used_today),
+        }
+    }
+
+    fn refill(&self, state: &mut QuotaState) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+        let capacity = f64::from(self.limits.hourly);
+        state.tokens = (state.tokens + elapsed * capacity / SECONDS_PER_HOUR).min(capacity);
+        state.last_refill = now;
+        let day = today();
+        if day != state.day {
+            state.day = day;
+            state.used_today = 0;
+        }
+    }
+}
+
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        / SECONDS_PER_DAY
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::funtranslations_api::quota::{QuotaLimits, QuotaTracker};
+
+    #[test]
+    fn try_acquire_stops_when_the_hourly_bucket_is_empty() {
+        let tracker = QuotaTracker::new(QuotaLimits {
+            hourly: 2,
+            daily: 10,
+        });
+
+        assert!(tracker.try_acquire());
+        assert!(tracker.try_acquire());
+        assert!(!tracker.try_acquire());
+        let status = tracker.status();
+        assert_eq!(status.hourly_remaining, 0);
+        assert_eq!(status.daily_remaining, 8);
+    }
+
+    #[test]
+    fn try_acquire_stops_when_the_daily_budget_is_spent() {
+        let tracker = QuotaTracker::new(QuotaLimits {
+            hourly: 10,
+            daily: 1,
+        });
+
+        assert!(tracker.try_acquire());
+        assert!(!tracker.try_acquire());
+        assert_eq!(tracker.status().hourly_remaining, 9);
+    }
+
+    #[test]
+    fn exhaust_empties_the_hourly_bucket() {
+        let tracker = QuotaTracker::new(QuotaLimits {
+            hourly: 5,
+            daily: 60,
+        });
+
+        tracker.exhaust();
+
+        assert!(!tracker.try_acquire());
+        assert_eq!(tracker.status().daily_remaining, 60);
+    }
+}
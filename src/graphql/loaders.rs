@@ -0,0 +1,133 @@
+use std::{collections::HashMap, convert::Infallible, sync::Arc};
+
+use async_graphql::dataloader::Loader;
+use futures::{stream, StreamExt};
+
+use crate::error::PokeError;
+use crate::poke_api::client::{PokeApiClientError, PokemonInfo};
+use crate::pokedex::service::Pokedex;
+use crate::translation::service::TranslationService;
+use crate::translation::translator::Translation;
+
+/// Upstream calls a loader makes at once, however large its batch.
+const LOAD_CONCURRENCY: usize = 8;
+
+/// Species by lowercase name. PokeAPI has no batch endpoint, so a batch is
+/// fetched concurrently, through the Pokédex caches; unknown names are left
+/// out of it.
+pub struct PokemonLoader {
+    pokedex: Arc<Pokedex>,
+}
+
+impl PokemonLoader {
+    pub fn new(pokedex: Arc<Pokedex>) -> Self {
+        Self { pokedex }
+    }
+}
+
+impl Loader<String> for PokemonLoader {
+    type Value = PokemonInfo;
+    type Error = PokeError;
+
+    async fn load(&self, names: &[String]) -> Result<HashMap<String, PokemonInfo>, PokeError> {
+        let pokedex = &self.pokedex;
+        let results = stream::iter(names.to_vec())
+            .map(|name| async move { pokedex.get_pokemon_info(&name).await })
+            .buffered(LOAD_CONCURRENCY)
+            .collect::<Vec<_>>()
+            .await;
+        let mut found = HashMap::new();
+        for (name, result) in names.iter().zip(results) {
+            match result {
+                Ok(pokemon_info) => {
+                    found.insert(name.clone(), pokemon_info);
+                }
+                Err(PokeApiClientError::NotFound) => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+        Ok(found)
+    }
+}
+
+/// Species names of evolution chains, by chain id.
+pub struct EvolutionChainLoader {
+    pokedex: Arc<Pokedex>,
+}
+
+impl EvolutionChainLoader {
+    pub fn new(pokedex: Arc<Pokedex>) -> Self {
+        Self { pokedex }
+    }
+}
+
+impl Loader<u32> for EvolutionChainLoader {
+    type Value = Arc<Vec<String>>;
+    type Error = PokeError;
+
+    async fn load(&self, ids: &[u32]) -> Result<HashMap<u32, Arc<Vec<String>>>, PokeError> {
+        let pokedex = &self.pokedex;
+        let results = stream::iter(ids.to_vec())
+            .map(|id| async move { pokedex.evolution_chain(id).await })
+            .buffered(LOAD_CONCURRENCY)
+            .collect::<Vec<_>>()
+            .await;
+        ids.iter()
+            .zip(results)
+            .map(|(id, result)| Ok((*id, result?)))
+            .collect()
+    }
+}
+
+/// Translations by `(dialect, text)`. Each is loaded on its own, so that
+/// running out of quota halfway through a batch only fails the rest.
+pub struct TranslationLoader {
+    translation_service: Arc<TranslationService>,
+}
+
+impl TranslationLoader {
+    pub fn new(translation_service: Arc<TranslationService>) -> Self {
+        Self {
+            translation_service,
+        }
+    }
+}
+
+impl TranslationLoader {
+    /// Whether any provider translates into `dialect`.
+    pub fn supports(&self, dialect: &str) -> bool {
+        self.translation_service
+            .dialects()
+            .iter()
+            .any(|info| info.dialect == dialect)
+    }
+}
+
+impl Loader<(String, String)> for TranslationLoader {
+    type Value = Result<Translation, PokeError>;
+    type Error = Infallible;
+
+    async fn load(
+        &self,
+        keys: &[(String, String)],
+    ) -> Result<HashMap<(String, String), Self::Value>, Infallible> {
+        let translation_service = &self.translation_service;
+        let results =
+            stream::iter(keys.to_vec())
+                .map(|(dialect, text)| async move {
+                    translation_service.translate(&dialect, &text).await
+                })
+                .buffered(LOAD_CONCURRENCY)
+                .collect::<Vec<_>>()
+                .await;
+        Ok(keys
+            .iter()
+            .cloned()
+            .zip(results.into_iter().map(|result| {
+                result
+                    .map(|chained| chained.translation)
+                    .map_err(PokeError::from)
+            }))
+            .collect())
+    }
+}
@@ -0,0 +1,2 @@
+pub mod loaders;
+pub mod schema;
@@ -0,0 +1,340 @@
+use std::sync::Arc;
+
+use async_graphql::{
+    dataloader::{DataLoader, HashMapCache},
+    ComplexObject, Context, EmptyMutation, EmptySubscription, ErrorExtensions, Object, Request,
+    Result, Schema,
+};
+
+use crate::error::PokeError;
+use crate::graphql::loaders::{EvolutionChainLoader, PokemonLoader, TranslationLoader};
+use crate::poke_api::client::PokemonInfo;
+use crate::pokedex::service::{dialect_for, Pokedex};
+use crate::translation::service::TranslationService;
+use crate::translation::translator::Translation;
+
+pub type PokedexSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// Loaders as given to each request, caching what they load for it.
+type CachingLoader<T> = DataLoader<T, HashMapCache>;
+
+/// Deep enough for `pokemons { evolutionChain { translation { .. } } }`.
+const MAX_DEPTH: usize = 6;
+/// Every field costs 1, lists multiply what they hold and translations
+/// count as `TRANSLATION_COMPLEXITY`, so a query may ask for about a hundred
+/// translated descriptions at most.
+const MAX_COMPLEXITY: usize = 1500;
+const TRANSLATION_COMPLEXITY: usize = 10;
+/// Longest evolution chain, Eevee's, as counted for complexity.
+const EVOLUTION_CHAIN_COMPLEXITY: usize = 9;
+const MAX_NAMES: usize = 100;
+
+/// Longest text the `translation` fields accept, in characters.
+struct MaxTextLength(usize);
+
+pub fn schema(translation_max_text_length: usize) -> PokedexSchema {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .data(MaxTextLength(translation_max_text_length))
+        .finish()
+}
+
+/// Gives `request` its own loaders, which batch and deduplicate the fetches
+/// of its resolvers. Caching across requests is left to the Pokédex and the
+/// translation service.
+pub fn with_loaders(
+    request: Request,
+    pokedex: Arc<Pokedex>,
+    translation_service: Arc<TranslationService>,
+) -> Request {
+    request
+        .data(DataLoader::with_cache(
+            PokemonLoader::new(pokedex.clone()),
+            actix_web::rt::spawn,
+            HashMapCache::default(),
+        ))
+        .data(DataLoader::with_cache(
+            EvolutionChainLoader::new(pokedex),
+            actix_web::rt::spawn,
+            HashMapCache::default(),
+        ))
+        .data(DataLoader::with_cache(
+            TranslationLoader::new(translation_service),
+            actix_web::rt::spawn,
+            HashMapCache::default(),
+        ))
+}
+
+/// Reports a `PokeError` with its `PE_*` code among the extensions.
+fn graphql_error(error: PokeError) -> async_graphql::Error {
+    async_graphql::Error::new(error.message.clone())
+        .extend_with(|_, extensions| extensions.set("code", error.code.as_str()))
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// A species by name or national dex number, null if there is none.
+    async fn pokemon(&self, ctx: &Context<'_>, name: String) -> Result<Option<PokemonInfo>> {
+        ctx.data_unchecked::<CachingLoader<PokemonLoader>>()
+            .load_one(name.to_lowercase())
+            .await
+            .map_err(graphql_error)
+    }
+
+    /// Several species in the order asked, null for those there are none of.
+    #[graphql(complexity = "names.len() * child_complexity")]
+    async fn pokemons(
+        &self,
+        ctx: &Context<'_>,
+        names: Vec<String>,
+    ) -> Result<Vec<Option<PokemonInfo>>> {
+        if names.is_empty() || names.len() > MAX_NAMES {
+            return Err(graphql_error(PokeError::bad_request(format!(
+                "names must list between 1 and {} species",
+                MAX_NAMES
+            ))));
+        }
+        let names = names
+            .iter()
+            .map(|name| name.to_lowercase())
+            .collect::<Vec<String>>();
+        let found = ctx
+            .data_unchecked::<CachingLoader<PokemonLoader>>()
+            .load_many(names.iter().cloned())
+            .await
+            .map_err(graphql_error)?;
+        Ok(names.iter().map(|name| found.get(name).cloned()).collect())
+    }
+
+    /// `text` translated into `dialect`.
+    #[graphql(complexity = "TRANSLATION_COMPLEXITY + child_complexity")]
+    async fn translation(
+        &self,
+        ctx: &Context<'_>,
+        dialect: String,
+        text: String,
+    ) -> Result<Translation> {
+        translate(ctx, dialect, text).await
+    }
+}
+
+#[ComplexObject]
+impl PokemonInfo {
+    /// Dialect the description is translated into by default: Yoda for cave
+    /// dwellers and legends, Shakespeare for everyone else.
+    async fn dialect(&self) -> &'static str {
+        dialect_for(self)
+    }
+
+    /// The description translated into `dialect`, by default the one the
+    /// species is assigned.
+    #[graphql(complexity = "TRANSLATION_COMPLEXITY + child_complexity")]
+    async fn translation(&self, ctx: &Context<'_>, dialect: Option<String>) -> Result<Translation> {
+        let dialect = dialect.unwrap_or_else(|| String::from(dialect_for(self)));
+        translate(ctx, dialect, self.description.clone()).await
+    }
+
+    /// Every species of the evolution chain, this one included, each before
+    /// those it evolves into.
+    #[graphql(complexity = "EVOLUTION_CHAIN_COMPLEXITY * child_complexity")]
+    async fn evolution_chain(&self, ctx: &Context<'_>) -> Result<Vec<PokemonInfo>> {
+        let id = match self.evolution_chain {
+            Some(id) => id,
+            None => return Ok(vec![self.clone()]),
+        };
+        let names = ctx
+            .data_unchecked::<CachingLoader<EvolutionChainLoader>>()
+            .load_one(id)
+            .await
+            .map_err(graphql_error)?
+            .unwrap_or_default();
+        let species = ctx
+            .data_unchecked::<CachingLoader<PokemonLoader>>()
+            .load_many(names.iter().cloned())
+            .await
+            .map_err(graphql_error)?;
+        Ok(names
+            .iter()
+            .filter_map(|name| species.get(name).cloned())
+            .collect())
+    }
+}
+
+/// Checks `text` and `dialect` the way `POST /translate` does, before any
+/// provider is called.
+async fn translate(ctx: &Context<'_>, dialect: String, text: String) -> Result<Translation> {
+    let dialect = dialect.to_lowercase();
+    let text = String::from(text.trim());
+    if text.is_empty() {
+        return Err(graphql_error(PokeError::bad_request(String::from(
+            "text must not be empty",
+        ))));
+    }
+    let MaxTextLength(max_text_length) = ctx.data_unchecked::<MaxTextLength>();
+    if text.chars().count() > *max_text_length {
+        return Err(graphql_error(PokeError::bad_request(format!(
+            "text must be at most {} characters long",
+            max_text_length
+        ))));
+    }
+    let supported = ctx
+        .data_unchecked::<CachingLoader<TranslationLoader>>()
+        .loader()
+        .supports(&dialect);
+    if !supported {
+        return Err(graphql_error(PokeError::bad_request(format!(
+            "unsupported dialect '{}'",
+            dialect
+        ))));
+    }
+    ctx.data_unchecked::<CachingLoader<TranslationLoader>>()
+        .load_one((dialect, text))
+        .await
+        .unwrap_or_else(|infallible| match infallible {})
+        .unwrap_or_else(|| Err(PokeError::internal()))
+        .map_err(graphql_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc, time::Duration};
+
+    use async_graphql::Request;
+    use serde_json::{json, Value};
+    use wiremock::{
+        matchers::{any, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use crate::{
+        cache::memory::TtlCache,
+        funtranslations_api::client::FunTranslationsApiClient,
+        graphql::schema::{schema, with_loaders},
+        poke_api::client::PokeApiClient,
+        pokedex::service::Pokedex,
+        translation::{
+            chain::{HealthPolicy, TranslatorChain},
+            registry::TranslatorRegistry,
+            service::TranslationService,
+        },
+    };
+
+    fn species(name: &str) -> Value {
+        json!({"name": name, "is_legendary": false, "evolution_chain": {"url": "https://pokeapi.co/api/v2/evolution-chain/67/"}, "flavor_text_entries": [{"flavor_text": format!("{} text.", name), "language": {"name": "en"}}]})
+    }
+
+    fn services(mock_server: &MockServer) -> (Arc<Pokedex>, Arc<TranslationService>) {
+        let pokedex = Arc::new(Pokedex::new(
+            PokeApiClient::new(mock_server.uri(), Duration::from_millis(200)),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        ));
+        let translation_service = Arc::new(TranslationService::new(
+            TranslatorChain::new(
+                TranslatorRegistry::new(
+                    vec![Arc::new(FunTranslationsApiClient::new(
+                        mock_server.uri(),
+                        Duration::from_millis(200),
+                    ))],
+                    &HashMap::new(),
+                )
+                .unwrap(),
+                vec![],
+                HealthPolicy {
+                    failure_threshold: 3,
+                    cooldown: Duration::from_secs(60),
+                },
+                HashMap::new(),
+            )
+            .unwrap(),
+            TtlCache::new(Duration::from_secs(60), 10),
+        ));
+        (pokedex, translation_service)
+    }
+
+    #[actix_rt::test]
+    async fn species_reached_twice_are_fetched_once() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        for name in ["eevee", "vaporeon"] {
+            Mock::given(path(format!("/pokemon-species/{}", name)))
+                .and(method("GET"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(species(name)))
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+        }
+        Mock::given(path("/evolution-chain/67"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"chain": {"species": {"name": "eevee"}, "evolves_to": [{"species": {"name": "vaporeon"}, "evolves_to": []}]}})))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let (pokedex, translation_service) = services(&mock_server);
+        let request = Request::new(
+            r#"{ pokemons(names: ["Eevee", "vaporeon", "missingno"]) { name dialect evolutionChain { name } } }"#,
+        );
+
+        // Act
+        let response = schema(1000)
+            .execute(with_loaders(request, pokedex, translation_service))
+            .await;
+
+        // Assert
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+        assert_eq!(
+            data,
+            json!({"pokemons": [
+                {"name": "eevee", "dialect": "shakespeare", "evolutionChain": [{"name": "eevee"}, {"name": "vaporeon"}]},
+                {"name": "vaporeon", "dialect": "shakespeare", "evolutionChain": [{"name": "eevee"}, {"name": "vaporeon"}]},
+                null,
+            ]})
+        );
+    }
+
+    #[actix_rt::test]
+    async fn oversized_queries_are_refused_before_reaching_upstream() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+        let (pokedex, translation_service) = services(&mock_server);
+        let names = (1..=101)
+            .map(|id| format!("\"{}\"", id))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let queries = [
+            format!("{{ pokemons(names: [{}]) {{ name }} }}", names),
+            format!(
+                r#"{{ translation(dialect: "yoda", text: "{}") {{ translated }} }}"#,
+                "a".repeat(1001)
+            ),
+            String::from(
+                r#"{ translation(dialect: "klingon", text: "Hello.") { translated } }"#,
+            ),
+            String::from(
+                "{ pokemons(names: [\"1\", \"2\", \"3\", \"4\", \"5\", \"6\", \"7\", \"8\", \"9\", \"10\", \"11\", \"12\", \"13\", \"14\", \"15\", \"16\", \"17\"]) { evolutionChain { translation { translated } } } }",
+            ),
+        ];
+
+        for query in queries {
+            // Act
+            let response = schema(1000)
+                .execute(with_loaders(
+                    Request::new(query),
+                    pokedex.clone(),
+                    translation_service.clone(),
+                ))
+                .await;
+
+            // Assert
+            assert!(!response.errors.is_empty());
+        }
+    }
+}
@@ -0,0 +1,3 @@
+pub mod pretranslate;
+pub mod translation;
+pub mod warmup;
@@ -0,0 +1,437 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use actix_web::rt::time;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::cache::disk::DiskNamespace;
+use crate::pokedex::service::{dialect_for, Pokedex, SpeciesFilter};
+use crate::translation::chain::is_retryable;
+use crate::translation::service::TranslationService;
+
+const CHECKPOINT_KEY: &str = "pretranslation";
+/// Times a species is tried before it counts as failed.
+const MAX_ATTEMPTS: u32 = 3;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PretranslationState {
+    Disabled,
+    Pending,
+    Running,
+    /// Out of quota, or the preferred provider is failing: waiting for the
+    /// next batch.
+    Waiting,
+    Done,
+}
+
+/// Position in the dex, saved after every species so that a restart picks
+/// up where the job stopped.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct Checkpoint {
+    total: usize,
+    processed: usize,
+    translated: usize,
+    cached: usize,
+    failed: usize,
+    /// Species that failed, with the attempts made so far, tried again once
+    /// the rest of the dex is done.
+    #[serde(default)]
+    requeued: Vec<(String, u32)>,
+}
+
+impl Checkpoint {
+    /// Species to go through, requeued ones included.
+    fn len(&self) -> usize {
+        self.total + self.requeued.len()
+    }
+
+    /// The next species in `names`, then in the requeued ones, with the
+    /// attempts already made.
+    fn next(&self, names: &[String]) -> (String, u32) {
+        match names.get(self.processed) {
+            Some(name) => (name.clone(), 0),
+            None => self.requeued[self.processed - self.total].clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct PretranslationProgress {
+    pub state: PretranslationState,
+    pub total: usize,
+    pub processed: usize,
+    pub translated: usize,
+    /// Species whose description was already translated.
+    pub cached: usize,
+    pub failed: usize,
+    /// Estimated from the pace kept since startup.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eta_secs: Option<u64>,
+}
+
+enum Step {
+    Translated,
+    Cached,
+    Failed,
+    /// To be retried in a later batch.
+    Deferred,
+}
+
+/// Long-running job translating the description of every species in the dex
+/// into its dialect, a batch at a time, without ever leaving the preferred
+/// provider short of quota. Translations land in the translation caches,
+/// persistent ones included.
+pub struct Pretranslation {
+    state: PretranslationState,
+    batch_size: usize,
+    interval: Duration,
+    quota_reserve: u32,
+    checkpoints: Option<DiskNamespace>,
+    progress: Mutex<PretranslationProgress>,
+}
+
+impl Pretranslation {
+    pub fn disabled() -> Self {
+        Self::with_state(PretranslationState::Disabled, 0, Duration::ZERO)
+    }
+
+    /// Translates up to `batch_size` descriptions every `interval`.
+    pub fn new(batch_size: usize, interval: Duration) -> Self {
+        Self::with_state(PretranslationState::Pending, batch_size.max(1), interval)
+    }
+
+    fn with_state(state: PretranslationState, batch_size: usize, interval: Duration) -> Self {
+        Self {
+            state,
+            batch_size,
+            interval,
+            quota_reserve: 0,
+            checkpoints: None,
+            progress: Mutex::new(PretranslationProgress {
+                state,
+                total: 0,
+                processed: 0,
+                translated: 0,
+                cached: 0,
+                failed: 0,
+                eta_secs: None,
+            }),
+        }
+    }
+
+    /// Leaves `reserve` calls of the preferred provider's budget to live
+    /// traffic.
+    pub fn with_quota_reserve(mut self, reserve: u32) -> Self {
+        self.quota_reserve = reserve;
+        self
+    }
+
+    /// Saves the job's position in `checkpoints`, so that it resumes after
+    /// a restart.
+    pub fn with_checkpoints(mut self, checkpoints: DiskNamespace) -> Self {
+        self.checkpoints = Some(checkpoints);
+        self
+    }
+
+    pub fn progress(&self) -> PretranslationProgress {
+        self.progress.lock().unwrap().clone()
+    }
+
+    pub async fn run(&self, pokedex: &Pokedex, translation_service: &TranslationService) {
+        if self.state != PretranslationState::Pending {
+            return;
+        }
+        self.update(|progress| progress.state = PretranslationState::Running);
+        let names = self.species_names(pokedex).await;
+        let saved = match &self.checkpoints {
+            Some(checkpoints) => checkpoints.get::<Checkpoint>(CHECKPOINT_KEY).await,
+            None => None,
+        };
+        let mut checkpoint = saved
+            .filter(|checkpoint| checkpoint.total == names.len())
+            .unwrap_or(Checkpoint {
+                total: names.len(),
+                ..Checkpoint::default()
+            });
+        self.report(&checkpoint, None);
+        let (started_at, resumed_from) = (Instant::now(), checkpoint.processed);
+        while checkpoint.processed < checkpoint.len() {
+            let mut spent = 0;
+            while spent < self.batch_size && checkpoint.processed < checkpoint.len() {
+                let (name, attempts) = checkpoint.next(&names);
+                match pretranslate(&name, pokedex, translation_service, self.quota_reserve).await {
+                    Step::Translated => {
+                        checkpoint.translated += 1;
+                        spent += 1;
+                    }
+                    Step::Cached => checkpoint.cached += 1,
+                    Step::Failed if attempts + 1 < MAX_ATTEMPTS => {
+                        checkpoint.requeued.push((name, attempts + 1))
+                    }
+                    Step::Failed => checkpoint.failed += 1,
+                    Step::Deferred => break,
+                }
+                checkpoint.processed += 1;
+                self.save(&checkpoint).await;
+            }
+            let eta = estimate(
+                started_at.elapsed(),
+                checkpoint.processed - resumed_from,
+                checkpoint.len() - checkpoint.processed,
+            );
+            self.report(&checkpoint, eta);
+            if checkpoint.processed < checkpoint.len() {
+                self.update(|progress| progress.state = PretranslationState::Waiting);
+                time::sleep(self.interval).await;
+                self.update(|progress| progress.state = PretranslationState::Running);
+            }
+        }
+        self.update(|progress| progress.state = PretranslationState::Done);
+    }
+
+    /// The whole dex, in dex order. Listing it is retried every interval
+    /// until PokeAPI answers.
+    async fn species_names(&self, pokedex: &Pokedex) -> Vec<String> {
+        loop {
+            match pokedex.find_species(&SpeciesFilter::default()).await {
+                Ok(species) => return species.into_iter().map(|species| species.name).collect(),
+                Err(_) => time::sleep(self.interval).await,
+            }
+        }
+    }
+
+    async fn save(&self, checkpoint: &Checkpoint) {
+        if let Some(checkpoints) = &self.checkpoints {
+            checkpoints.insert(CHECKPOINT_KEY, checkpoint.clone()).await;
+        }
+    }
+
+    fn report(&self, checkpoint: &Checkpoint, eta: Option<Duration>) {
+        self.update(|progress| {
+            progress.total = checkpoint.len();
+            progress.processed = checkpoint.processed;
+            progress.translated = checkpoint.translated;
+            progress.cached = checkpoint.cached;
+            progress.failed = checkpoint.failed;
+            progress.eta_secs = eta.map(|eta| eta.as_secs());
+        });
+    }
+
+    fn update(&self, change: impl FnOnce(&mut PretranslationProgress)) {
+        change(&mut self.progress.lock().unwrap());
+    }
+}
+
+async fn pretranslate(
+    name: &str,
+    pokedex: &Pokedex,
+    translation_service: &TranslationService,
+    quota_reserve: u32,
+) -> Step {
+    let pokemon_info = match pokedex.get_pokemon_info(name).await {
+        Ok(pokemon_info) => pokemon_info,
+        Err(_) => return Step::Failed,
+    };
+    let dialect = dialect_for(&pokemon_info);
+    if translation_service
+        .is_cached(dialect, &pokemon_info.description)
+        .await
+    {
+        return Step::Cached;
+    }
+    if !translation_service.has_quota_left(dialect, quota_reserve) {
+        return Step::Deferred;
+    }
+    match translation_service
+        .translate(dialect, &pokemon_info.description)
+        .await
+    {
+        Ok(chained) if chained.preferred => Step::Translated,
+        // A fallback answered: it is not cached, the preferred provider gets
+        // another chance later.
+        Ok(_) => Step::Deferred,
+        Err(error) if is_retryable(&error) => Step::Deferred,
+        Err(_) => Step::Failed,
+    }
+}
+
+/// Time left at the pace of `done` species in `elapsed`.
+fn estimate(elapsed: Duration, done: usize, remaining: usize) -> Option<Duration> {
+    if remaining == 0 {
+        return Some(Duration::ZERO);
+    }
+    if done == 0 {
+        return None;
+    }
+    Some(elapsed.mul_f64(remaining as f64 / done as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, env, fs, sync::Arc, time::Duration};
+
+    use serde_json::{json, Value};
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use crate::{
+        cache::{disk::DiskCache, memory::TtlCache},
+        jobs::pretranslate::{Checkpoint, Pretranslation, PretranslationState, CHECKPOINT_KEY},
+        poke_api::client::PokeApiClient,
+        pokedex::service::Pokedex,
+        translation::{
+            chain::{HealthPolicy, TranslatorChain},
+            local::LocalTranslator,
+            registry::TranslatorRegistry,
+            service::TranslationService,
+        },
+    };
+
+    fn species_json(name: &str) -> Value {
+        json!({"flavor_text_entries":[{"flavor_text":format!("{} lives in caves.", name),"language":{"name":"en","url":"https://pokeapi.co/api/v2/language/9/"},"version":{"name":"red","url":"https://pokeapi.co/api/v2/version/1/"}}],"habitat":{"name":"cave","url":"https://pokeapi.co/api/v2/pokemon-habitat/1/"},"is_legendary":false,"name":name})
+    }
+
+    fn local_translation_service() -> TranslationService {
+        TranslationService::new(
+            TranslatorChain::new(
+                TranslatorRegistry::new(vec![Arc::new(LocalTranslator)], &HashMap::new()).unwrap(),
+                vec![String::from("local")],
+                HealthPolicy {
+                    failure_threshold: 3,
+                    cooldown: Duration::from_secs(60),
+                },
+                HashMap::new(),
+            )
+            .unwrap(),
+            TtlCache::new(Duration::from_secs(60), 10),
+        )
+    }
+
+    #[tokio::test]
+    async fn run_retries_failed_species_once_the_dex_is_done() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/pokemon-species"))
+            .and(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({"count": 2, "results": [
+                    {"name": "zubat", "url": "https://pokeapi.co/api/v2/pokemon-species/41/"},
+                    {"name": "onix", "url": "https://pokeapi.co/api/v2/pokemon-species/95/"},
+                ]})),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/pokemon-species/zubat"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        for name in ["zubat", "onix"] {
+            Mock::given(path(format!("/pokemon-species/{}", name)))
+                .and(method("GET"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(species_json(name)))
+                .mount(&mock_server)
+                .await;
+        }
+        let pokedex = Pokedex::new(
+            PokeApiClient::new(mock_server.uri(), Duration::from_millis(200)),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        );
+        let translation_service = local_translation_service();
+        let job = Pretranslation::new(10, Duration::from_millis(5));
+
+        // Act
+        job.run(&pokedex, &translation_service).await;
+
+        // Assert
+        let progress = job.progress();
+        assert_eq!(progress.state, PretranslationState::Done);
+        assert_eq!(progress.translated, 2);
+        assert_eq!(progress.failed, 0);
+        assert!(
+            translation_service
+                .is_cached("yoda", "zubat lives in caves.")
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn run_resumes_from_the_saved_checkpoint() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let dex = ["zubat", "golbat", "onix"];
+        let results = dex
+            .iter()
+            .map(
+                |name| json!({"name": name, "url": "https://pokeapi.co/api/v2/pokemon-species/1/"}),
+            )
+            .collect::<Vec<Value>>();
+        Mock::given(path("/pokemon-species"))
+            .and(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({"count": 3, "results": results})),
+            )
+            .mount(&mock_server)
+            .await;
+        for (i, name) in dex.iter().enumerate() {
+            Mock::given(path(format!("/pokemon-species/{}", name)))
+                .and(method("GET"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(species_json(name)))
+                .expect(if i == 0 { 0 } else { 1 })
+                .mount(&mock_server)
+                .await;
+        }
+        let pokedex = Pokedex::new(
+            PokeApiClient::new(mock_server.uri(), Duration::from_millis(200)),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        );
+        let translation_service = local_translation_service();
+        let cache_path = env::temp_dir().join(format!(
+            "yap-pretranslation-{}-{}.sqlite",
+            std::process::id(),
+            rand::random::<u32>()
+        ));
+        let disk_cache = Arc::new(DiskCache::open(&cache_path).unwrap());
+        let checkpoints = disk_cache.persistent_namespace("jobs");
+        checkpoints
+            .insert(
+                CHECKPOINT_KEY,
+                Checkpoint {
+                    total: 3,
+                    processed: 1,
+                    translated: 1,
+                    ..Checkpoint::default()
+                },
+            )
+            .await;
+        let job = Pretranslation::new(1, Duration::from_millis(5))
+            .with_checkpoints(disk_cache.persistent_namespace("jobs"));
+
+        // Act
+        job.run(&pokedex, &translation_service).await;
+        let saved = checkpoints.get::<Checkpoint>(CHECKPOINT_KEY).await;
+        fs::remove_file(&cache_path).unwrap();
+
+        // Assert
+        let progress = job.progress();
+        assert_eq!(progress.state, PretranslationState::Done);
+        assert_eq!(progress.processed, 3);
+        assert_eq!(progress.translated, 3);
+        assert_eq!(progress.eta_secs, Some(0));
+        assert!(
+            translation_service
+                .is_cached("yoda", "onix lives in caves.")
+                .await
+        );
+        assert_eq!(saved.unwrap().processed, 3);
+    }
+}
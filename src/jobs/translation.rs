@@ -0,0 +1,395 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use actix_web::rt::time;
+use futures::{
+    channel::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    StreamExt,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::cache::memory::TtlCache;
+use crate::error::PokeError;
+use crate::poke_api::client::PokemonInfo;
+use crate::pokedex::service::dialect_for;
+use crate::translation::service::TranslationService;
+use crate::translation::translator::TranslationError;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Pending,
+    Done,
+    Failed,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct TranslationJob {
+    pub id: String,
+    pub pokemon: String,
+    pub status: JobStatus,
+    /// The translated `PokemonInfo`, once done.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<PokemonInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<PokeError>,
+}
+
+/// Queued jobs allowed by default before new ones are refused.
+const DEFAULT_QUEUE_CAPACITY: usize = 1000;
+/// Attempts made by default before a job is failed.
+const DEFAULT_MAX_ATTEMPTS: u32 = 12;
+
+/// Translations accepted while no provider could serve them, retried in the
+/// background until one can, or until they run out of attempts. Pending jobs
+/// are kept until they finish; finished jobs are kept for `retention`, and
+/// each is posted to the webhook, if configured.
+pub struct TranslationJobs {
+    enabled: bool,
+    retry_interval: Duration,
+    max_attempts: u32,
+    queue_capacity: usize,
+    pending: Mutex<HashMap<String, TranslationJob>>,
+    finished: TtlCache<TranslationJob>,
+    webhook: Option<Webhook>,
+    sender: UnboundedSender<QueuedJob>,
+    receiver: Mutex<Option<UnboundedReceiver<QueuedJob>>>,
+}
+
+struct QueuedJob {
+    id: String,
+    pokemon_info: PokemonInfo,
+    /// Attempts made so far.
+    attempts: u32,
+}
+
+struct Webhook {
+    url: String,
+    http_client: reqwest::Client,
+}
+
+impl TranslationJobs {
+    pub fn disabled() -> Self {
+        Self::with_state(false, Duration::ZERO, TtlCache::new(Duration::ZERO, 0))
+    }
+
+    pub fn new(retry_interval: Duration, retention: Duration, max_jobs: usize) -> Self {
+        Self::with_state(true, retry_interval, TtlCache::new(retention, max_jobs))
+    }
+
+    fn with_state(
+        enabled: bool,
+        retry_interval: Duration,
+        finished: TtlCache<TranslationJob>,
+    ) -> Self {
+        let (sender, receiver) = mpsc::unbounded();
+        Self {
+            enabled,
+            retry_interval,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            queue_capacity: DEFAULT_QUEUE_CAPACITY,
+            pending: Mutex::new(HashMap::new()),
+            finished,
+            webhook: None,
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+        }
+    }
+
+    /// Posts every finished job to `url`.
+    pub fn with_webhook(mut self, url: String, timeout: Duration) -> Self {
+        self.webhook = Some(Webhook {
+            url,
+            http_client: reqwest::Client::builder().timeout(timeout).build().unwrap(),
+        });
+        self
+    }
+
+    /// Refuses new jobs while `queue_capacity` are pending.
+    pub fn with_queue_capacity(mut self, queue_capacity: usize) -> Self {
+        self.queue_capacity = queue_capacity;
+        self
+    }
+
+    /// Fails a job once `max_attempts` translations of it have failed.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Whether a translation that failed with `error` may be queued rather
+    /// than failing the request: only when providers are out of budget or
+    /// all skipped, which a later attempt can fix.
+    pub fn accepts(&self, error: &TranslationError) -> bool {
+        self.enabled
+            && matches!(
+                error,
+                TranslationError::RateLimited
+                    | TranslationError::QuotaExhausted
+                    | TranslationError::Unavailable
+            )
+    }
+
+    /// Queues the translation of the description of `pokemon_info`, unless
+    /// the queue is full.
+    pub fn submit(&self, pokemon_info: PokemonInfo) -> Result<TranslationJob, PokeError> {
+        let job = TranslationJob {
+            id: format!("{:032x}", rand::random::<u128>()),
+            pokemon: pokemon_info.name.clone(),
+            status: JobStatus::Pending,
+            result: None,
+            error: None,
+        };
+        {
+            let mut pending = self.pending.lock().unwrap();
+            if pending.len() >= self.queue_capacity {
+                return Err(PokeError::queue_full());
+            }
+            pending.insert(job.id.clone(), job.clone());
+        }
+        let _ = self.sender.unbounded_send(QueuedJob {
+            id: job.id.clone(),
+            pokemon_info,
+            attempts: 0,
+        });
+        Ok(job)
+    }
+
+    pub fn get(&self, id: &str) -> Option<TranslationJob> {
+        if let Some(job) = self.pending.lock().unwrap().get(id) {
+            return Some(job.clone());
+        }
+        self.finished
+            .get_with_age(id)
+            .filter(|(_, age)| *age < self.finished.ttl())
+            .map(|(job, _)| job)
+    }
+
+    /// Works through the queued jobs one attempt at a time, for as long as
+    /// the queue lives. A job that can be retried goes back to the end of
+    /// the queue after `retry_interval`, so that it holds up no other.
+    pub async fn run(&self, translation_service: &TranslationService) {
+        let mut receiver = match self.receiver.lock().unwrap().take() {
+            Some(receiver) if self.enabled => receiver,
+            _ => return,
+        };
+        while let Some(queued) = receiver.next().await {
+            let job = match self.attempt(queued, translation_service).await {
+                Some(job) => job,
+                None => continue,
+            };
+            self.pending.lock().unwrap().remove(&job.id);
+            self.finished.insert(job.id.clone(), job.clone());
+            if let Some(webhook) = &self.webhook {
+                // Best effort: the job can still be polled.
+                let _ = webhook
+                    .http_client
+                    .post(&webhook.url)
+                    .json(&job)
+                    .send()
+                    .await;
+            }
+        }
+    }
+
+    /// Translates the job once, returning it if it finished and requeueing
+    /// it otherwise.
+    async fn attempt(
+        &self,
+        mut queued: QueuedJob,
+        translation_service: &TranslationService,
+    ) -> Option<TranslationJob> {
+        let dialect = dialect_for(&queued.pokemon_info);
+        let mut job = TranslationJob {
+            id: queued.id.clone(),
+            pokemon: queued.pokemon_info.name.clone(),
+            status: JobStatus::Pending,
+            result: None,
+            error: None,
+        };
+        queued.attempts += 1;
+        match translation_service
+            .translate(dialect, &queued.pokemon_info.description)
+            .await
+        {
+            Ok(chained) => {
+                queued.pokemon_info.description = chained.translation.translated;
+                job.status = JobStatus::Done;
+                job.result = Some(queued.pokemon_info);
+            }
+            Err(error) if self.accepts(&error) && queued.attempts < self.max_attempts => {
+                let (sender, retry_interval) = (self.sender.clone(), self.retry_interval);
+                tokio::spawn(async move {
+                    time::sleep(retry_interval).await;
+                    let _ = sender.unbounded_send(queued);
+                });
+                return None;
+            }
+            Err(error) => {
+                job.status = JobStatus::Failed;
+                job.error = Some(PokeError::from(error));
+            }
+        }
+        Some(job)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc, time::Duration};
+
+    use actix_web::rt::time;
+    use serde_json::Value;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use crate::{
+        cache::memory::TtlCache,
+        funtranslations_api::client::FunTranslationsApiClient,
+        jobs::translation::{JobStatus, TranslationJobs},
+        poke_api::client::PokemonInfo,
+        translation::{
+            chain::{HealthPolicy, TranslatorChain},
+            registry::TranslatorRegistry,
+            service::TranslationService,
+        },
+    };
+
+    fn translation_service(base_url: String) -> Arc<TranslationService> {
+        Arc::new(TranslationService::new(
+            TranslatorChain::new(
+                TranslatorRegistry::new(
+                    vec![Arc::new(FunTranslationsApiClient::new(
+                        base_url,
+                        Duration::from_millis(200),
+                    ))],
+                    &HashMap::new(),
+                )
+                .unwrap(),
+                vec![],
+                HealthPolicy {
+                    failure_threshold: 3,
+                    cooldown: Duration::from_secs(60),
+                },
+                HashMap::new(),
+            )
+            .unwrap(),
+            TtlCache::new(Duration::from_secs(60), 10),
+        ))
+    }
+
+    fn mewtwo() -> PokemonInfo {
+        PokemonInfo {
+            id: Some(150),
+            name: String::from("mewtwo"),
+            description: String::from("It was created by a scientist."),
+            habitat: Some(String::from("rare")),
+            generation: Some(String::from("generation-i")),
+            is_legendary: true,
+            is_mythical: false,
+            evolution_chain: Some(67),
+        }
+    }
+
+    #[tokio::test]
+    async fn run_retries_queued_jobs_and_notifies_the_webhook() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/translate/yoda.json"))
+            .respond_with(ResponseTemplate::new(429))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/translate/yoda.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{"success":{"total":1},"contents":{"translated":"Created by a scientist, it was.","text":"It was created by a scientist.","translation":"yoda"}}"#,
+            ))
+            .mount(&mock_server)
+            .await;
+        let webhook_receiver = MockServer::start().await;
+        Mock::given(path("/hooks/translations"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&webhook_receiver)
+            .await;
+        let translation_service = translation_service(mock_server.uri());
+        let jobs = Arc::new(
+            TranslationJobs::new(Duration::from_millis(10), Duration::from_secs(60), 10)
+                .with_webhook(
+                    format!("{}/hooks/translations", webhook_receiver.uri()),
+                    Duration::from_millis(200),
+                ),
+        );
+        let job = jobs.submit(mewtwo()).unwrap();
+
+        // Act
+        let worker = {
+            let (jobs, translation_service) = (jobs.clone(), translation_service.clone());
+            tokio::spawn(async move { jobs.run(&translation_service).await })
+        };
+        let mut notified = Vec::new();
+        for _ in 0..50 {
+            notified = webhook_receiver.received_requests().await.unwrap();
+            if !notified.is_empty() {
+                break;
+            }
+            time::sleep(Duration::from_millis(10)).await;
+        }
+        let polled = jobs.get(&job.id).unwrap();
+        worker.abort();
+
+        // Assert
+        assert_eq!(job.status, JobStatus::Pending);
+        assert_eq!(polled.status, JobStatus::Done);
+        assert_eq!(
+            polled.result.unwrap().description,
+            "Created by a scientist, it was."
+        );
+        assert_eq!(notified.len(), 1);
+        let body: Value = serde_json::from_slice(&notified[0].body).unwrap();
+        assert_eq!(body["id"], job.id.as_str());
+        assert_eq!(body["status"], "done");
+    }
+
+    #[tokio::test]
+    async fn jobs_are_refused_when_the_queue_is_full_and_failed_after_their_last_attempt() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/translate/yoda.json"))
+            .respond_with(ResponseTemplate::new(429))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        let translation_service = translation_service(mock_server.uri());
+        let jobs = Arc::new(
+            TranslationJobs::new(Duration::from_millis(10), Duration::from_secs(60), 10)
+                .with_queue_capacity(1)
+                .with_max_attempts(2),
+        );
+
+        // Act
+        let job = jobs.submit(mewtwo()).unwrap();
+        let refused = jobs.submit(mewtwo());
+        let worker = {
+            let (jobs, translation_service) = (jobs.clone(), translation_service.clone());
+            tokio::spawn(async move { jobs.run(&translation_service).await })
+        };
+        let mut polled = jobs.get(&job.id).unwrap();
+        for _ in 0..50 {
+            polled = jobs.get(&job.id).unwrap();
+            if polled.status != JobStatus::Pending {
+                break;
+            }
+            time::sleep(Duration::from_millis(10)).await;
+        }
+        worker.abort();
+
+        // Assert
+        assert_eq!(refused.unwrap_err().status_code, 429);
+        assert_eq!(polled.status, JobStatus::Failed);
+        assert_eq!(polled.error.unwrap().code, "PE_RATE_LIMITED");
+    }
+}
@@ -0,0 +1,267 @@
+use std::sync::Mutex;
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::pokedex::service::{dialect_for, Pokedex, SpeciesFilter};
+use crate::translation::service::TranslationService;
+
+/// Warmed when no species are configured: the first generation holds the
+/// most requested Pokémon.
+const DEFAULT_GENERATION: &str = "generation-i";
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WarmupState {
+    Disabled,
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct WarmupProgress {
+    pub state: WarmupState,
+    pub total: usize,
+    pub warmed: usize,
+    pub translated: usize,
+    pub failed: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Startup job filling the species cache and, as far as the preferred
+/// provider's quota allows, the translation cache. The service reports
+/// itself ready once the job is over, whatever its outcome.
+pub struct Warmup {
+    species: Vec<String>,
+    quota_reserve: u32,
+    progress: Mutex<WarmupProgress>,
+}
+
+impl Warmup {
+    pub fn disabled() -> Self {
+        Self::with_state(Vec::new(), WarmupState::Disabled)
+    }
+
+    /// Warms `species`, or the whole first generation when empty.
+    pub fn new(species: Vec<String>) -> Self {
+        Self::with_state(species, WarmupState::Pending)
+    }
+
+    fn with_state(species: Vec<String>, state: WarmupState) -> Self {
+        Self {
+            species,
+            quota_reserve: 0,
+            progress: Mutex::new(WarmupProgress {
+                state,
+                total: 0,
+                warmed: 0,
+                translated: 0,
+                failed: 0,
+                error: None,
+            }),
+        }
+    }
+
+    /// Leaves `reserve` calls of the preferred provider's budget to live
+    /// traffic.
+    pub fn with_quota_reserve(mut self, reserve: u32) -> Self {
+        self.quota_reserve = reserve;
+        self
+    }
+
+    pub fn progress(&self) -> WarmupProgress {
+        self.progress.lock().unwrap().clone()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        !matches!(
+            self.progress.lock().unwrap().state,
+            WarmupState::Pending | WarmupState::Running
+        )
+    }
+
+    pub async fn run(&self, pokedex: &Pokedex, translation_service: &TranslationService) {
+        if self.progress().state != WarmupState::Pending {
+            return;
+        }
+        self.update(|progress| progress.state = WarmupState::Running);
+        let names = if self.species.is_empty() {
+            let filter = SpeciesFilter {
+                generation: Some(String::from(DEFAULT_GENERATION)),
+                ..SpeciesFilter::default()
+            };
+            match pokedex.find_species(&filter).await {
+                Ok(species) => species.into_iter().map(|species| species.name).collect(),
+                Err(_) => {
+                    self.update(|progress| {
+                        progress.state = WarmupState::Failed;
+                        progress.error =
+                            Some(format!("cannot list {} species", DEFAULT_GENERATION));
+                    });
+                    return;
+                }
+            }
+        } else {
+            self.species.clone()
+        };
+        self.update(|progress| progress.total = names.len());
+        for name in names {
+            let pokemon_info = match pokedex.get_pokemon_info(&name).await {
+                Ok(pokemon_info) => pokemon_info,
+                Err(_) => {
+                    self.update(|progress| progress.failed += 1);
+                    continue;
+                }
+            };
+            self.update(|progress| progress.warmed += 1);
+            let dialect = dialect_for(&pokemon_info);
+            if translation_service.has_quota_left(dialect, self.quota_reserve) {
+                let translated = translation_service
+                    .translate(dialect, &pokemon_info.description)
+                    .await
+                    .is_ok_and(|chained| chained.preferred);
+                if translated {
+                    self.update(|progress| progress.translated += 1);
+                }
+            }
+        }
+        self.update(|progress| progress.state = WarmupState::Done);
+    }
+
+    fn update(&self, change: impl FnOnce(&mut WarmupProgress)) {
+        change(&mut self.progress.lock().unwrap());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc, time::Duration};
+
+    use serde_json::json;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use crate::{
+        cache::memory::TtlCache,
+        jobs::warmup::{Warmup, WarmupState},
+        poke_api::client::PokeApiClient,
+        pokedex::service::Pokedex,
+        translation::{
+            chain::{HealthPolicy, Quota, TranslatorChain},
+            local::LocalTranslator,
+            registry::TranslatorRegistry,
+            service::TranslationService,
+        },
+    };
+
+    #[tokio::test]
+    async fn run_warms_the_configured_species_and_reports_progress() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let species = json!({"flavor_text_entries":[{"flavor_text":"It was created by a scientist.","language":{"name":"en","url":"https://pokeapi.co/api/v2/language/9/"},"version":{"name":"red","url":"https://pokeapi.co/api/v2/version/1/"}}],"habitat":{"name":"rare","url":"https://pokeapi.co/api/v2/pokemon-habitat/5/"},"is_legendary":true,"name":"mewtwo"});
+        Mock::given(path("/pokemon-species/mewtwo"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(species))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/pokemon-species/missingno"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let pokedex = Pokedex::new(
+            PokeApiClient::new(mock_server.uri(), Duration::from_millis(200)),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        );
+        let translation_service = TranslationService::new(
+            TranslatorChain::new(
+                TranslatorRegistry::new(vec![Arc::new(LocalTranslator)], &HashMap::new()).unwrap(),
+                vec![String::from("local")],
+                HealthPolicy {
+                    failure_threshold: 3,
+                    cooldown: Duration::from_secs(60),
+                },
+                HashMap::new(),
+            )
+            .unwrap(),
+            TtlCache::new(Duration::from_secs(60), 10),
+        );
+        let warmup = Warmup::new(vec![String::from("mewtwo"), String::from("missingno")]);
+        assert!(!warmup.is_ready());
+
+        // Act
+        warmup.run(&pokedex, &translation_service).await;
+
+        assert!(warmup.is_ready());
+        let progress = warmup.progress();
+        assert_eq!(progress.state, WarmupState::Done);
+        assert_eq!(progress.total, 2);
+        assert_eq!(progress.warmed, 1);
+        assert_eq!(progress.translated, 1);
+        assert_eq!(progress.failed, 1);
+        assert_eq!(
+            translation_service
+                .translate("yoda", "It was created by a scientist.")
+                .await
+                .unwrap()
+                .attempts,
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn run_leaves_the_quota_reserve_to_live_traffic() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let species = json!({"flavor_text_entries":[{"flavor_text":"It was created by a scientist.","language":{"name":"en","url":"https://pokeapi.co/api/v2/language/9/"},"version":{"name":"red","url":"https://pokeapi.co/api/v2/version/1/"}}],"habitat":{"name":"rare","url":"https://pokeapi.co/api/v2/pokemon-habitat/5/"},"is_legendary":true,"name":"mewtwo"});
+        Mock::given(path("/pokemon-species/mewtwo"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(species))
+            .mount(&mock_server)
+            .await;
+        let pokedex = Pokedex::new(
+            PokeApiClient::new(mock_server.uri(), Duration::from_millis(200)),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        );
+        let mut quotas = HashMap::new();
+        quotas.insert(
+            String::from("local"),
+            Quota {
+                limit: 1,
+                window: Duration::from_secs(60),
+            },
+        );
+        let translation_service = TranslationService::new(
+            TranslatorChain::new(
+                TranslatorRegistry::new(vec![Arc::new(LocalTranslator)], &HashMap::new()).unwrap(),
+                vec![String::from("local")],
+                HealthPolicy {
+                    failure_threshold: 3,
+                    cooldown: Duration::from_secs(60),
+                },
+                quotas,
+            )
+            .unwrap(),
+            TtlCache::new(Duration::from_secs(60), 10),
+        );
+        let warmup = Warmup::new(vec![String::from("mewtwo")]).with_quota_reserve(1);
+
+        // Act
+        warmup.run(&pokedex, &translation_service).await;
+
+        // Assert
+        let progress = warmup.progress();
+        assert_eq!(progress.warmed, 1);
+        assert_eq!(progress.translated, 0);
+        assert!(translation_service.has_quota_left("yoda", 0));
+    }
+}
@@ -0,0 +1,232 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+
+use crate::translation::translator::{Capabilities, Translation, TranslationError, Translator};
+
+/// Client for a self-hosted LibreTranslate compatible API. Descriptions are
+/// always english, so `en` is the source language and the dialect is the
+/// target language code.
+pub struct LibreTranslateApiClient {
+    http_client: Client,
+    base_url: String,
+    languages: Vec<String>,
+}
+
+impl LibreTranslateApiClient {
+    pub fn new(base_url: String, timeout: Duration, languages: Vec<String>) -> Self {
+        let http_client = Client::builder().timeout(timeout).build().unwrap();
+        Self {
+            http_client,
+            base_url,
+            languages,
+        }
+    }
+
+    pub async fn translate(
+        &self,
+        language: &str,
+        text: &str,
+    ) -> Result<Translation, LibreTranslateApiClientError> {
+        let url = format!("{}/translate", self.base_url);
+        let response = self
+            .http_client
+            .post(url)
+            .json(&json!({
+                "q": text,
+                "source": "en",
+                "target": language,
+                "format": "text",
+            }))
+            .send()
+            .await?;
+        match response.status() {
+            StatusCode::OK => {
+                let json = response.text().await?;
+                self.build_translation(language, text, &json)
+            }
+            StatusCode::BAD_REQUEST => {
+                let json = response.text().await?;
+                let message = serde_json::from_str::<Value>(&json)
+                    .ok()
+                    .and_then(|parsed| parsed["error"].as_str().map(String::from))
+                    .unwrap_or_else(|| String::from("bad request"));
+                Err(LibreTranslateApiClientError::BadRequest { message })
+            }
+            StatusCode::NOT_FOUND => Err(LibreTranslateApiClientError::NotFound),
+            StatusCode::TOO_MANY_REQUESTS => Err(LibreTranslateApiClientError::TooManyRequests),
+            _ => Err(LibreTranslateApiClientError::InternalError),
+        }
+    }
+
+    fn build_translation(
+        &self,
+        language: &str,
+        text: &str,
+        json: &str,
+    ) -> Result<Translation, LibreTranslateApiClientError> {
+        let parsed = serde_json::from_str::<Value>(json)?;
+        match parsed["translatedText"].as_str() {
+            Some(translated) => Ok(Translation {
+                dialect: String::from(language),
+                original: String::from(text),
+                translated: String::from(translated),
+                engine: String::from("libretranslate"),
+            }),
+            None => Err(LibreTranslateApiClientError::InternalError),
+        }
+    }
+}
+
+#[async_trait]
+impl Translator for LibreTranslateApiClient {
+    fn name(&self) -> &str {
+        "libretranslate"
+    }
+
+    async fn translate(&self, dialect: &str, text: &str) -> Result<Translation, TranslationError> {
+        Ok(LibreTranslateApiClient::translate(self, dialect, text).await?)
+    }
+
+    fn supported_dialects(&self) -> Vec<String> {
+        self.languages.clone()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            remote: true,
+            rate_limited: false,
+            max_text_length: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LibreTranslateApiClientError {
+    InternalError,
+    NotFound,
+    TooManyRequests,
+    Timeout,
+    BadRequest { message: String },
+}
+
+impl From<LibreTranslateApiClientError> for TranslationError {
+    fn from(error: LibreTranslateApiClientError) -> Self {
+        match error {
+            LibreTranslateApiClientError::InternalError => TranslationError::InternalError,
+            LibreTranslateApiClientError::NotFound => TranslationError::NotFound,
+            LibreTranslateApiClientError::TooManyRequests => TranslationError::RateLimited,
+            LibreTranslateApiClientError::Timeout => TranslationError::Timeout,
+            LibreTranslateApiClientError::BadRequest { message } => {
+                TranslationError::BadRequest { message }
+            }
+        }
+    }
+}
+
+impl From<serde_json::Error> for LibreTranslateApiClientError {
+    fn from(_error: serde_json::Error) -> Self {
+        LibreTranslateApiClientError::InternalError
+    }
+}
+
+impl From<reqwest::Error> for LibreTranslateApiClientError {
+    fn from(error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            LibreTranslateApiClientError::Timeout
+        } else {
+            LibreTranslateApiClientError::InternalError
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use claim::{assert_err, assert_ok};
+    use serde_json::json;
+    use wiremock::{
+        matchers::{body_json, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use crate::libretranslate_api::client::{
+        LibreTranslateApiClient, LibreTranslateApiClientError,
+    };
+
+    #[tokio::test]
+    async fn translate_posts_the_text_to_base_url() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let libretranslate_api_client = LibreTranslateApiClient::new(
+            mock_server.uri(),
+            Duration::from_millis(200),
+            vec![String::from("it")],
+        );
+
+        Mock::given(path("/translate"))
+            .and(method("POST"))
+            .and(body_json(json!({
+                "q": "Master Obiwan has lost a planet.",
+                "source": "en",
+                "target": "it",
+                "format": "text",
+            })))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(
+                    json!({"translatedText": "Il maestro Obiwan ha perso un pianeta."}),
+                ),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let translation = libretranslate_api_client
+            .translate("it", "Master Obiwan has lost a planet.")
+            .await;
+
+        assert_ok!(&translation);
+        let translation = translation.unwrap();
+        assert_eq!(translation.dialect, "it");
+        assert_eq!(translation.original, "Master Obiwan has lost a planet.");
+        assert_eq!(
+            translation.translated,
+            "Il maestro Obiwan ha perso un pianeta."
+        );
+    }
+
+    #[tokio::test]
+    async fn translate_fails_if_the_server_returns_400() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let libretranslate_api_client = LibreTranslateApiClient::new(
+            mock_server.uri(),
+            Duration::from_millis(200),
+            vec![String::from("it")],
+        );
+
+        Mock::given(path("/translate"))
+            .and(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(400).set_body_json(json!({"error": "xx is not supported"})),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let translation = libretranslate_api_client.translate("xx", "Hello").await;
+
+        assert_err!(&translation);
+        assert_eq!(
+            translation.unwrap_err(),
+            LibreTranslateApiClientError::BadRequest {
+                message: String::from("xx is not supported")
+            }
+        );
+    }
+}
@@ -1,141 +1,234 @@
+mod cache;
+mod configuration;
+mod error;
+mod format;
 mod funtranslations_api;
+mod graphql;
+mod jobs;
+mod libretranslate_api;
 mod poke_api;
+mod pokedex;
+mod problem;
+mod routes;
+mod translation;
+
+use std::{sync::Arc, time::Duration};
 
 use actix_web::{
-    get, http,
-    http::header,
     web::{self, Data},
-    App, HttpResponse, HttpResponseBuilder, HttpServer, ResponseError,
+    App, HttpServer,
 };
-use funtranslations_api::client::{FunTranslationsApiClient, FunTranslationsApiClientError};
-use poke_api::client::{PokeApiClient, PokeApiClientError};
-use serde::{Deserialize, Serialize};
-use std::{
-    fmt::{self, Display},
-    time::Duration,
+use cache::{disk::DiskCache, memory::TtlCache, stale::StalePolicy};
+use configuration::Settings;
+use format::ContentNegotiation;
+use funtranslations_api::{client::FunTranslationsApiClient, quota::QuotaLimits};
+use jobs::{pretranslate::Pretranslation, translation::TranslationJobs, warmup::Warmup};
+use libretranslate_api::client::LibreTranslateApiClient;
+use poke_api::client::PokeApiClient;
+use pokedex::service::Pokedex;
+use problem::ProblemDetails;
+use translation::{
+    chain::{HealthPolicy, Quota, TranslatorChain},
+    local::LocalTranslator,
+    registry::TranslatorRegistry,
+    service::TranslationService,
+    translator::Translator,
 };
 
-#[derive(Deserialize)]
-struct PathParams {
-    name: String,
-}
-
-#[derive(Debug, Serialize)]
-struct PokeError {
-    #[serde(skip_serializing)]
-    status_code: u16,
-    code: String,
-    message: String,
-}
-
-impl Display for PokeError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}: {}", self.code, self.message)
-    }
-}
-
-impl ResponseError for PokeError {
-    fn status_code(&self) -> http::StatusCode {
-        http::StatusCode::from_u16(self.status_code).unwrap()
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let settings = Settings::from_env();
+    let disk_cache =
+        settings.disk_cache_path.as_ref().map(|path| {
+            Arc::new(DiskCache::open(path).unwrap_or_else(|error| {
+                panic!("cannot open disk cache {}: {}", path.display(), error)
+            }))
+        });
+    let stale_policy = StalePolicy {
+        while_revalidate: settings.stale_while_revalidate,
+        if_error: settings.stale_if_error,
+    };
+    let mut pokedex = Pokedex::new(
+        PokeApiClient::new(settings.poke_api_url.clone(), settings.upstream_timeout),
+        settings.species_index_ttl,
+        settings.species_ttl,
+    )
+    .with_stale_policy(stale_policy)
+    .with_negative_cache(settings.not_found_ttl, settings.not_found_max_entries)
+    .with_legendary_scan_limit(settings.legendary_scan_limit);
+    let mut translation_service = translation_service(&settings, stale_policy);
+    let mut pretranslation = if settings.pretranslation_enabled {
+        Pretranslation::new(
+            settings.pretranslation_batch_size,
+            settings.pretranslation_interval,
+        )
+        .with_quota_reserve(settings.pretranslation_quota_reserve)
+    } else {
+        Pretranslation::disabled()
+    };
+    if let Some(disk_cache) = &disk_cache {
+        pokedex = pokedex.with_disk_cache(disk_cache.namespace(
+            "species",
+            settings.disk_cache_species_ttl,
+            settings.disk_cache_species_max_entries,
+        ));
+        translation_service = translation_service.with_disk_cache(disk_cache.namespace(
+            "translation",
+            settings.disk_cache_translation_ttl,
+            settings.disk_cache_translation_max_entries,
+        ));
+        pretranslation = pretranslation.with_checkpoints(disk_cache.persistent_namespace("jobs"));
+        actix_web::rt::spawn(compact_periodically(
+            disk_cache.clone(),
+            settings.disk_cache_compaction_interval,
+        ));
     }
-
-    fn error_response(&self) -> HttpResponse {
-        HttpResponseBuilder::new(self.status_code())
-            .insert_header(header::ContentType(mime::APPLICATION_JSON))
-            .body(serde_json::to_string(&self).unwrap())
+    let pokedex = Data::new(pokedex);
+    let translation_service = Data::new(translation_service);
+    let warmup = Data::new(if settings.warmup_enabled {
+        Warmup::new(settings.warmup_species.clone())
+            .with_quota_reserve(settings.warmup_quota_reserve)
+    } else {
+        Warmup::disabled()
+    });
+    {
+        let (warmup, pokedex, translation_service) =
+            (warmup.clone(), pokedex.clone(), translation_service.clone());
+        actix_web::rt::spawn(async move { warmup.run(&pokedex, &translation_service).await });
     }
-}
-
-impl From<PokeApiClientError> for PokeError {
-    fn from(error: PokeApiClientError) -> Self {
-        match error {
-            PokeApiClientError::BadRequest { message } => PokeError {
-                status_code: http::StatusCode::BAD_REQUEST.as_u16(),
-                code: String::from("PE_BAD_REQUEST"),
-                message: message,
-            },
-            PokeApiClientError::InternalError => PokeError {
-                status_code: http::StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
-                code: String::from("PE_INTERNAL"),
-                message: String::from("internal error"),
-            },
-            PokeApiClientError::NotFound => PokeError {
-                status_code: http::StatusCode::NOT_FOUND.as_u16(),
-                code: String::from("PE_NOT_FOUND"),
-                message: String::from("pokemon not found"),
-            },
-        }
+    let mut jobs = if settings.async_jobs_enabled {
+        TranslationJobs::new(
+            settings.job_retry_interval,
+            settings.job_retention,
+            settings.max_jobs,
+        )
+        .with_queue_capacity(settings.max_pending_jobs)
+        .with_max_attempts(settings.job_max_attempts)
+    } else {
+        TranslationJobs::disabled()
+    };
+    if let Some(job_webhook_url) = &settings.job_webhook_url {
+        jobs = jobs.with_webhook(job_webhook_url.clone(), settings.upstream_timeout);
     }
-}
-
-impl From<FunTranslationsApiClientError> for PokeError {
-    fn from(error: FunTranslationsApiClientError) -> Self {
-        match error {
-            FunTranslationsApiClientError::BadRequest { message } => PokeError {
-                status_code: http::StatusCode::BAD_REQUEST.as_u16(),
-                code: String::from("PE_BAD_REQUEST"),
-                message: message,
-            },
-            FunTranslationsApiClientError::NotFound => PokeError {
-                status_code: http::StatusCode::NOT_FOUND.as_u16(),
-                code: String::from("PE_NOT_FOUND"),
-                message: String::from("not found"),
-            },
-            FunTranslationsApiClientError::InternalError => PokeError {
-                status_code: http::StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
-                code: String::from("PE_INTERNAL"),
-                message: String::from("internal error"),
-            },
-        }
+    let jobs = Data::new(jobs);
+    {
+        let (jobs, translation_service) = (jobs.clone(), translation_service.clone());
+        actix_web::rt::spawn(async move { jobs.run(&translation_service).await });
     }
-}
-
-#[get("/pokemon/{name}")]
-async fn get_pokemon_info(
-    info: web::Path<PathParams>,
-    poke_api_client: web::Data<PokeApiClient>,
-) -> Result<HttpResponse, PokeError> {
-    let pokemon_info = poke_api_client.get_pokemon_info(&info.name).await?;
-    Ok(HttpResponse::Ok().json(pokemon_info))
-}
-
-#[get("/pokemon/translated/{name}")]
-async fn get_pokemon_info_translated(
-    info: web::Path<PathParams>,
-    poke_api_client: web::Data<PokeApiClient>,
-    funtranslations_api_client: web::Data<FunTranslationsApiClient>,
-) -> Result<HttpResponse, PokeError> {
-    let mut pokemon_info = poke_api_client.get_pokemon_info(&info.name).await?;
-    let mut dialect = "shakespeare";
-    if pokemon_info.habitat == "cave" || pokemon_info.is_legendary {
-        dialect = "yoda";
+    let pretranslation = Data::new(pretranslation);
+    {
+        let (pretranslation, pokedex, translation_service) = (
+            pretranslation.clone(),
+            pokedex.clone(),
+            translation_service.clone(),
+        );
+        actix_web::rt::spawn(
+            async move { pretranslation.run(&pokedex, &translation_service).await },
+        );
     }
-    let translation = funtranslations_api_client
-        .translate(dialect, &pokemon_info.description)
-        .await?;
-    pokemon_info.description = String::from(translation.translated);
-    Ok(HttpResponse::Ok().json(pokemon_info))
-}
-
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    let poke_api_client = Data::new(PokeApiClient::new(
-        String::from("https://pokeapi.co/api/v2"),
-        Duration::from_secs(10),
-    ));
-    let funtranslations_api_client = Data::new(FunTranslationsApiClient::new(
-        String::from("https://api.funtranslations.com"),
-        Duration::from_secs(10),
+    let bind_address = settings.bind_address.clone();
+    let settings = Data::new(settings);
+    let schema = Data::new(graphql::schema::schema(
+        settings.translation_max_text_length,
     ));
 
     HttpServer::new(move || {
         App::new()
-            .service(get_pokemon_info)
-            .service(get_pokemon_info_translated)
-            .app_data(poke_api_client.clone())
-            .app_data(funtranslations_api_client.clone())
+            .wrap(ProblemDetails::new(
+                settings.error_format,
+                &settings.problem_type_base,
+            ))
+            // Outermost, to also convert the error bodies rendered above.
+            .wrap(ContentNegotiation)
+            .configure(routes::configure)
+            .app_data(routes::translate::json_config(
+                settings.translation_max_text_length,
+            ))
+            .app_data(pokedex.clone())
+            .app_data(translation_service.clone())
+            .app_data(jobs.clone())
+            .app_data(warmup.clone())
+            .app_data(pretranslation.clone())
+            .app_data(settings.clone())
+            .app_data(schema.clone())
     })
-    .bind("127.0.0.1:8080")?
+    .bind(bind_address)?
     .run()
     .await
 }
+
+async fn compact_periodically(disk_cache: Arc<DiskCache>, period: Duration) {
+    let mut interval = actix_web::rt::time::interval(period);
+    loop {
+        interval.tick().await;
+        let disk_cache = disk_cache.clone();
+        // Compaction is best effort; a failed run is retried on the next tick.
+        let _ = web::block(move || disk_cache.compact()).await;
+    }
+}
+
+fn translation_service(settings: &Settings, stale_policy: StalePolicy) -> TranslationService {
+    let mut funtranslations_api_client = FunTranslationsApiClient::new(
+        settings.funtranslations_api_url.clone(),
+        settings.upstream_timeout,
+    )
+    .with_quota(QuotaLimits {
+        hourly: settings.funtranslations_hourly_quota,
+        daily: settings.funtranslations_daily_quota,
+    })
+    .with_post_threshold(settings.funtranslations_post_threshold);
+    if let Some(api_secret) = &settings.funtranslations_api_secret {
+        funtranslations_api_client = funtranslations_api_client.with_api_secret(api_secret);
+    }
+    let mut translators: Vec<Arc<dyn Translator>> = vec![Arc::new(funtranslations_api_client)];
+    if let Some(libretranslate_api_url) = &settings.libretranslate_api_url {
+        translators.push(Arc::new(LibreTranslateApiClient::new(
+            libretranslate_api_url.clone(),
+            settings.upstream_timeout,
+            settings.libretranslate_languages.clone(),
+        )));
+    }
+    translators.push(Arc::new(LocalTranslator));
+    let registry = TranslatorRegistry::new(translators, &settings.translation_routes)
+        .unwrap_or_else(|error| panic!("invalid translation routes: {}", error));
+    // Providers that are not configured, like libretranslate without an url,
+    // are left out of the chain rather than rejected.
+    let order = settings
+        .translation_chain
+        .iter()
+        .filter(|provider| registry.get(provider).is_some())
+        .cloned()
+        .collect();
+    let quotas = settings
+        .translation_quotas
+        .iter()
+        .map(|(provider, (limit, window))| {
+            (
+                provider.clone(),
+                Quota {
+                    limit: *limit,
+                    window: *window,
+                },
+            )
+        })
+        .collect();
+    let chain = TranslatorChain::new(
+        registry,
+        order,
+        HealthPolicy {
+            failure_threshold: settings.translation_failure_threshold,
+            cooldown: settings.translation_cooldown,
+        },
+        quotas,
+    )
+    .unwrap_or_else(|error| panic!("invalid translation chain: {}", error));
+    TranslationService::new(
+        chain,
+        TtlCache::new(
+            settings.translation_cache_ttl,
+            settings.translation_cache_max_entries,
+        )
+        .with_grace_period(stale_policy.grace_period()),
+    )
+    .with_stale_policy(stale_policy)
+}
@@ -1,15 +1,62 @@
 use std::time::Duration;
 
-use reqwest::{Client, StatusCode};
-use serde::Serialize;
+use async_graphql::SimpleObject;
+use reqwest::{
+    header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    Client, StatusCode,
+};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use utoipa::ToSchema;
 
-#[derive(Debug, Serialize)]
+// Large enough to fetch the whole national dex in a single page.
+const SPECIES_INDEX_LIMIT: u32 = 100_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, SimpleObject)]
+#[graphql(complex)]
 pub struct PokemonInfo {
+    /// National dex number.
+    pub id: Option<u32>,
     pub name: String,
     pub description: String,
-    pub habitat: String,
+    /// Species introduced after the third generation have none.
+    pub habitat: Option<String>,
+    /// Generation that introduced the species, e.g. `generation-i`.
+    pub generation: Option<String>,
     pub is_legendary: bool,
+    pub is_mythical: bool,
+    /// Id of the evolution chain the species belongs to.
+    #[graphql(skip)]
+    pub evolution_chain: Option<u32>,
+}
+
+/// How a species looks, from its default form.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Appearance {
+    /// Url of the front sprite.
+    pub sprite: Option<String>,
+    /// Type names, primary first.
+    pub types: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SpeciesRef {
+    pub id: u32,
+    pub name: String,
+}
+
+/// Upstream cache validators of a resource, sent back to revalidate it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum Conditional<T> {
+    Modified(T, Validators),
+    /// The copy matching the validators sent is still current.
+    NotModified,
 }
 
 pub struct PokeApiClient {
@@ -26,14 +73,118 @@ impl PokeApiClient {
         }
     }
 
-    pub async fn get_pokemon_info(&self, name: &str) -> Result<PokemonInfo, PokeApiClientError> {
+    /// Fetches a species unless the copy described by `validators` is still
+    /// current, sparing the download of its rather large payload.
+    pub async fn get_pokemon_info_if_modified(
+        &self,
+        name: &str,
+        validators: &Validators,
+    ) -> Result<Conditional<PokemonInfo>, PokeApiClientError> {
+        validate_identifier("pokemon", name)?;
         let url = format!("{}/pokemon-species/{}", self.base_url, name);
-        let response = self.http_client.get(url).send().await?;
+        let mut request = self.http_client.get(url);
+        if let Some(etag) = &validators.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        let response = request.send().await?;
         match response.status() {
             StatusCode::OK => {
+                let header = |name| {
+                    response
+                        .headers()
+                        .get(name)
+                        .and_then(|value| value.to_str().ok())
+                        .map(String::from)
+                };
+                let validators = Validators {
+                    etag: header(ETAG),
+                    last_modified: header(LAST_MODIFIED),
+                };
                 let json = response.text().await?;
-                self.build_pokemon_info(&json)
+                Ok(Conditional::Modified(
+                    self.build_pokemon_info(&json)?,
+                    validators,
+                ))
             }
+            StatusCode::NOT_MODIFIED => Ok(Conditional::NotModified),
+            StatusCode::NOT_FOUND => Err(PokeApiClientError::NotFound),
+            _ => Err(PokeApiClientError::InternalError),
+        }
+    }
+
+    pub async fn get_species_index(&self) -> Result<Vec<SpeciesRef>, PokeApiClientError> {
+        let url = format!("{}/pokemon-species", self.base_url);
+        let json = self
+            .fetch(&url, &[("offset", 0), ("limit", SPECIES_INDEX_LIMIT)])
+            .await?;
+        let parsed = serde_json::from_str::<Value>(&json)?;
+        build_species_refs(&parsed["results"])
+    }
+
+    pub async fn get_habitat_species(
+        &self,
+        habitat: &str,
+    ) -> Result<Vec<SpeciesRef>, PokeApiClientError> {
+        validate_identifier("habitat", habitat)?;
+        let url = format!("{}/pokemon-habitat/{}", self.base_url, habitat);
+        let json = self.fetch(&url, &[]).await?;
+        let parsed = serde_json::from_str::<Value>(&json)?;
+        build_species_refs(&parsed["pokemon_species"])
+    }
+
+    pub async fn get_generation_species(
+        &self,
+        generation: &str,
+    ) -> Result<Vec<SpeciesRef>, PokeApiClientError> {
+        validate_identifier("generation", generation)?;
+        let url = format!("{}/generation/{}", self.base_url, generation);
+        let json = self.fetch(&url, &[]).await?;
+        let parsed = serde_json::from_str::<Value>(&json)?;
+        build_species_refs(&parsed["pokemon_species"])
+    }
+
+    /// Sprite and types of the default form of a species, which shares its
+    /// name and national dex number.
+    pub async fn get_appearance(&self, name: &str) -> Result<Appearance, PokeApiClientError> {
+        validate_identifier("pokemon", name)?;
+        let url = format!("{}/pokemon/{}", self.base_url, name);
+        let json = self.fetch(&url, &[]).await?;
+        let parsed = serde_json::from_str::<Value>(&json)?;
+        let mut types = parsed["types"]
+            .as_array()
+            .ok_or(PokeApiClientError::InternalError)?
+            .iter()
+            .filter_map(|entry| Some((entry["slot"].as_u64()?, entry["type"]["name"].as_str()?)))
+            .collect::<Vec<(u64, &str)>>();
+        types.sort_by_key(|(slot, _)| *slot);
+        Ok(Appearance {
+            sprite: parsed["sprites"]["front_default"]
+                .as_str()
+                .map(String::from),
+            types: types
+                .into_iter()
+                .map(|(_, name)| String::from(name))
+                .collect(),
+        })
+    }
+
+    /// Species names of an evolution chain, from its first stage on.
+    pub async fn get_evolution_chain(&self, id: u32) -> Result<Vec<String>, PokeApiClientError> {
+        let url = format!("{}/evolution-chain/{}", self.base_url, id);
+        let json = self.fetch(&url, &[]).await?;
+        let parsed = serde_json::from_str::<Value>(&json)?;
+        let mut species = Vec::new();
+        build_evolution_chain(&parsed["chain"], &mut species)?;
+        Ok(species)
+    }
+
+    async fn fetch(&self, url: &str, query: &[(&str, u32)]) -> Result<String, PokeApiClientError> {
+        let response = self.http_client.get(url).query(query).send().await?;
+        match response.status() {
+            StatusCode::OK => Ok(response.text().await?),
             StatusCode::NOT_FOUND => Err(PokeApiClientError::NotFound),
             _ => Err(PokeApiClientError::InternalError),
         }
@@ -44,24 +195,25 @@ impl PokeApiClient {
         let name = parsed["name"].as_str();
         let description = parsed["flavor_text_entries"]
             .as_array()
-            .unwrap()
-            .into_iter()
-            .find(|desc| desc["language"]["name"].as_str().unwrap().eq("en"))
-            .unwrap()["flavor_text"]
-            .as_str();
-        let habitat = parsed["habitat"]["name"].as_str();
+            .and_then(|entries| {
+                entries
+                    .iter()
+                    .find(|desc| desc["language"]["name"].as_str() == Some("en"))
+            })
+            .and_then(|desc| desc["flavor_text"].as_str());
         let is_legendary = parsed["is_legendary"].as_bool();
-        if name
-            .and(description)
-            .and(habitat)
-            .and(is_legendary)
-            .is_some()
-        {
+        if name.and(description).and(is_legendary).is_some() {
             Ok(PokemonInfo {
+                id: parsed["id"].as_u64().map(|id| id as u32),
                 name: String::from(name.unwrap()),
                 description: String::from(description.unwrap()).replace('\n', " "),
-                habitat: String::from(habitat.unwrap()),
+                habitat: parsed["habitat"]["name"].as_str().map(String::from),
+                generation: parsed["generation"]["name"].as_str().map(String::from),
                 is_legendary: is_legendary.unwrap(),
+                is_mythical: parsed["is_mythical"].as_bool().unwrap_or(false),
+                evolution_chain: parsed["evolution_chain"]["url"]
+                    .as_str()
+                    .and_then(resource_id),
             })
         } else {
             Err(PokeApiClientError::InternalError)
@@ -69,6 +221,68 @@ impl PokeApiClient {
     }
 }
 
+/// Accepts either a species name or a positive national dex number, so that
+/// nothing but a single path segment is ever appended to the upstream url.
+fn validate_identifier(kind: &str, identifier: &str) -> Result<(), PokeApiClientError> {
+    let valid_chars = !identifier.is_empty()
+        && identifier
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if !valid_chars {
+        return Err(PokeApiClientError::BadRequest {
+            message: format!("invalid {} '{}'", kind, identifier),
+        });
+    }
+    if let Ok(0) = identifier.parse::<u32>() {
+        return Err(PokeApiClientError::BadRequest {
+            message: format!("invalid {} id '{}'", kind, identifier),
+        });
+    }
+    Ok(())
+}
+
+/// Parses a list of PokeAPI named resources (`{"name": .., "url": ..}`),
+/// taking the id from the last segment of the resource url.
+fn build_species_refs(list: &Value) -> Result<Vec<SpeciesRef>, PokeApiClientError> {
+    let entries = list.as_array().ok_or(PokeApiClientError::InternalError)?;
+    let mut species = entries
+        .iter()
+        .map(|entry| {
+            let name = entry["name"].as_str();
+            let id = entry["url"].as_str().and_then(resource_id);
+            match (id, name) {
+                (Some(id), Some(name)) => Ok(SpeciesRef {
+                    id,
+                    name: String::from(name),
+                }),
+                _ => Err(PokeApiClientError::InternalError),
+            }
+        })
+        .collect::<Result<Vec<SpeciesRef>, PokeApiClientError>>()?;
+    species.sort_by_key(|species| species.id);
+    Ok(species)
+}
+
+/// Id of a PokeAPI resource, the last segment of its url.
+fn resource_id(url: &str) -> Option<u32> {
+    url.trim_end_matches('/').rsplit('/').next()?.parse().ok()
+}
+
+/// Species names of an evolution chain, each before those it evolves into.
+fn build_evolution_chain(
+    link: &Value,
+    species: &mut Vec<String>,
+) -> Result<(), PokeApiClientError> {
+    let name = link["species"]["name"]
+        .as_str()
+        .ok_or(PokeApiClientError::InternalError)?;
+    species.push(String::from(name));
+    for next in link["evolves_to"].as_array().into_iter().flatten() {
+        build_evolution_chain(next, species)?;
+    }
+    Ok(())
+}
+
 #[derive(Debug, PartialEq)]
 pub enum PokeApiClientError {
     NotFound,
@@ -77,13 +291,13 @@ pub enum PokeApiClientError {
 }
 
 impl From<serde_json::Error> for PokeApiClientError {
-    fn from(error: serde_json::Error) -> Self {
+    fn from(_error: serde_json::Error) -> Self {
         PokeApiClientError::InternalError
     }
 }
 
 impl From<reqwest::Error> for PokeApiClientError {
-    fn from(error: reqwest::Error) -> Self {
+    fn from(_error: reqwest::Error) -> Self {
         PokeApiClientError::InternalError
     }
 }
@@ -96,14 +310,30 @@ mod tests {
     use fake::{Fake, Faker};
     use serde_json::json;
     use wiremock::{
-        matchers::{any, method, path},
+        matchers::{any, header, method, path, query_param},
         Mock, MockServer, ResponseTemplate,
     };
 
-    use crate::poke_api::client::{PokeApiClient, PokeApiClientError};
+    use crate::poke_api::client::{
+        Conditional, PokeApiClient, PokeApiClientError, PokemonInfo, SpeciesRef, Validators,
+    };
+
+    /// Fetches `name` with no copy to revalidate.
+    async fn fetch(
+        poke_api_client: &PokeApiClient,
+        name: &str,
+    ) -> Result<PokemonInfo, PokeApiClientError> {
+        match poke_api_client
+            .get_pokemon_info_if_modified(name, &Validators::default())
+            .await?
+        {
+            Conditional::Modified(pokemon_info, _) => Ok(pokemon_info),
+            Conditional::NotModified => panic!("nothing to revalidate"),
+        }
+    }
 
     #[tokio::test]
-    async fn get_pokemon_info_fires_a_request_to_base_url() {
+    async fn get_pokemon_info_if_modified_fires_a_request_to_base_url() {
         // Arrange
         let mock_server = MockServer::start().await;
         let poke_api_client = PokeApiClient::new(mock_server.uri(), Duration::from_millis(200));
@@ -120,18 +350,85 @@ mod tests {
             .await;
 
         // Act
-        let info = poke_api_client.get_pokemon_info(&pokemon).await;
+        let info = fetch(&poke_api_client, &pokemon).await;
 
         assert_ok!(&info);
         let info = info.unwrap();
         assert_eq!(info.name, "mewtwo");
-        assert_eq!(info.habitat, "rare");
-        assert_eq!(info.is_legendary, true);
+        assert_eq!(info.habitat.as_deref(), Some("rare"));
+        assert!(info.is_legendary);
         assert_eq!(info.description, "It was created by a scientist after years of horrific gene splicing and DNA engineering experiments.");
     }
 
     #[tokio::test]
-    async fn get_pokemon_info_fails_if_the_server_returns_404() {
+    async fn get_pokemon_info_if_modified_returns_the_upstream_validators() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let poke_api_client = PokeApiClient::new(mock_server.uri(), Duration::from_millis(200));
+
+        let json_body = json!({"flavor_text_entries":[{"flavor_text":"A description.","language":{"name":"en","url":"https://pokeapi.co/api/v2/language/9/"},"version":{"name":"red","url":"https://pokeapi.co/api/v2/version/1/"}}],"habitat":{"name":"rare","url":"https://pokeapi.co/api/v2/pokemon-habitat/5/"},"is_legendary":true,"name":"mewtwo"});
+        Mock::given(path("/pokemon-species/mewtwo"))
+            .and(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"abc\"")
+                    .insert_header("Last-Modified", "Wed, 21 Oct 2015 07:28:00 GMT")
+                    .set_body_json(json_body),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let info = poke_api_client
+            .get_pokemon_info_if_modified("mewtwo", &Validators::default())
+            .await;
+
+        assert_ok!(&info);
+        match info.unwrap() {
+            Conditional::Modified(info, validators) => {
+                assert_eq!(info.name, "mewtwo");
+                assert_eq!(validators.etag.as_deref(), Some("\"abc\""));
+                assert_eq!(
+                    validators.last_modified.as_deref(),
+                    Some("Wed, 21 Oct 2015 07:28:00 GMT")
+                );
+            }
+            Conditional::NotModified => panic!("expected a modified species"),
+        }
+    }
+
+    #[tokio::test]
+    async fn get_pokemon_info_if_modified_sends_the_validators() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let poke_api_client = PokeApiClient::new(mock_server.uri(), Duration::from_millis(200));
+
+        Mock::given(path("/pokemon-species/mewtwo"))
+            .and(method("GET"))
+            .and(header("If-None-Match", "\"abc\""))
+            .respond_with(ResponseTemplate::new(304))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let info = poke_api_client
+            .get_pokemon_info_if_modified(
+                "mewtwo",
+                &Validators {
+                    etag: Some(String::from("\"abc\"")),
+                    last_modified: None,
+                },
+            )
+            .await;
+
+        assert_ok!(&info);
+        assert!(matches!(info.unwrap(), Conditional::NotModified));
+    }
+
+    #[tokio::test]
+    async fn get_pokemon_info_if_modified_fails_if_the_server_returns_404() {
         // Arrange
         let mock_server = MockServer::start().await;
         let poke_api_client = PokeApiClient::new(mock_server.uri(), Duration::from_millis(200));
@@ -145,7 +442,7 @@ mod tests {
             .await;
 
         // Act
-        let info = poke_api_client.get_pokemon_info(&pokemon).await;
+        let info = fetch(&poke_api_client, &pokemon).await;
 
         assert_err!(&info);
         let error = info.unwrap_err();
@@ -153,7 +450,28 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn get_pokemon_info_fails_if_the_server_returns_500() {
+    async fn get_pokemon_info_if_modified_fails_if_the_species_has_no_english_description() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let poke_api_client = PokeApiClient::new(mock_server.uri(), Duration::from_millis(200));
+
+        let json_body = json!({"flavor_text_entries":[{"flavor_text":"Une description.","language":{"name":"fr","url":"https://pokeapi.co/api/v2/language/5/"}}],"is_legendary":false,"name":"sprigatito"});
+        Mock::given(path("/pokemon-species/sprigatito"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json_body))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let info = fetch(&poke_api_client, "sprigatito").await;
+
+        assert_err!(&info);
+        assert_eq!(info.unwrap_err(), PokeApiClientError::InternalError);
+    }
+
+    #[tokio::test]
+    async fn get_pokemon_info_if_modified_fails_if_the_server_returns_500() {
         // Arrange
         let mock_server = MockServer::start().await;
         let poke_api_client = PokeApiClient::new(mock_server.uri(), Duration::from_millis(200));
@@ -167,7 +485,7 @@ mod tests {
             .await;
 
         // Act
-        let info = poke_api_client.get_pokemon_info(&pokemon).await;
+        let info = fetch(&poke_api_client, &pokemon).await;
 
         assert_err!(&info);
         let error = info.unwrap_err();
@@ -175,7 +493,7 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn get_pokemon_info_fails_if_the_server_take_too_much_time() {
+    async fn get_pokemon_info_if_modified_fails_if_the_server_take_too_much_time() {
         // Arrange
         let mock_server = MockServer::start().await;
         let poke_api_client = PokeApiClient::new(mock_server.uri(), Duration::from_millis(200));
@@ -193,8 +511,157 @@ mod tests {
             .await;
 
         // Act
-        let info = poke_api_client.get_pokemon_info(&pokemon).await;
+        let info = fetch(&poke_api_client, &pokemon).await;
 
         assert_err!(&info);
     }
+
+    #[tokio::test]
+    async fn get_pokemon_info_if_modified_accepts_numeric_ids() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let poke_api_client = PokeApiClient::new(mock_server.uri(), Duration::from_millis(200));
+
+        let json_body = json!({"flavor_text_entries":[{"flavor_text":"It was created by a scientist after years of horrific gene splicing and DNA engineering experiments.","language":{"name":"en","url":"https://pokeapi.co/api/v2/language/9/"},"version":{"name":"red","url":"https://pokeapi.co/api/v2/version/1/"}}],"habitat":{"name":"rare","url":"https://pokeapi.co/api/v2/pokemon-habitat/5/"},"is_legendary":true,"name":"mewtwo"});
+        Mock::given(path("/pokemon-species/150"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json_body))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let info = fetch(&poke_api_client, "150").await;
+
+        assert_ok!(&info);
+        assert_eq!(info.unwrap().name, "mewtwo");
+    }
+
+    #[tokio::test]
+    async fn get_pokemon_info_if_modified_rejects_invalid_identifiers_without_calling_the_server() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let poke_api_client = PokeApiClient::new(mock_server.uri(), Duration::from_millis(200));
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        for identifier in &["", "0", "../generation/1", "mr mime"] {
+            // Act
+            let info = fetch(&poke_api_client, identifier).await;
+
+            assert_err!(&info);
+            assert!(matches!(
+                info.unwrap_err(),
+                PokeApiClientError::BadRequest { .. }
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn get_species_index_uses_the_list_endpoint() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let poke_api_client = PokeApiClient::new(mock_server.uri(), Duration::from_millis(200));
+
+        let json_body = json!({"count":2,"next":null,"previous":null,"results":[{"name":"ivysaur","url":"https://pokeapi.co/api/v2/pokemon-species/2/"},{"name":"bulbasaur","url":"https://pokeapi.co/api/v2/pokemon-species/1/"}]});
+        Mock::given(path("/pokemon-species"))
+            .and(method("GET"))
+            .and(query_param("offset", "0"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json_body))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let index = poke_api_client.get_species_index().await;
+
+        assert_ok!(&index);
+        assert_eq!(
+            index.unwrap(),
+            vec![
+                SpeciesRef {
+                    id: 1,
+                    name: String::from("bulbasaur")
+                },
+                SpeciesRef {
+                    id: 2,
+                    name: String::from("ivysaur")
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_appearance_reads_the_sprite_and_types_in_slot_order() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let poke_api_client = PokeApiClient::new(mock_server.uri(), Duration::from_millis(200));
+
+        let json_body = json!({"id":1,"name":"bulbasaur","sprites":{"front_default":"https://raw.githubusercontent.com/PokeAPI/sprites/master/sprites/pokemon/1.png"},"types":[{"slot":2,"type":{"name":"poison","url":"https://pokeapi.co/api/v2/type/4/"}},{"slot":1,"type":{"name":"grass","url":"https://pokeapi.co/api/v2/type/12/"}}]});
+        Mock::given(path("/pokemon/1"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json_body))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let appearance = poke_api_client.get_appearance("1").await;
+
+        // Assert
+        assert_ok!(&appearance);
+        let appearance = appearance.unwrap();
+        assert_eq!(
+            appearance.sprite.as_deref(),
+            Some("https://raw.githubusercontent.com/PokeAPI/sprites/master/sprites/pokemon/1.png")
+        );
+        assert_eq!(appearance.types, vec!["grass", "poison"]);
+    }
+
+    #[tokio::test]
+    async fn get_evolution_chain_lists_every_stage_before_its_evolutions() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let poke_api_client = PokeApiClient::new(mock_server.uri(), Duration::from_millis(200));
+
+        let json_body = json!({"id":67,"chain":{"species":{"name":"eevee"},"evolves_to":[{"species":{"name":"vaporeon"},"evolves_to":[]},{"species":{"name":"jolteon"},"evolves_to":[]}]}});
+        Mock::given(path("/evolution-chain/67"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json_body))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let chain = poke_api_client.get_evolution_chain(67).await;
+
+        // Assert
+        assert_ok!(&chain);
+        assert_eq!(chain.unwrap(), vec!["eevee", "vaporeon", "jolteon"]);
+    }
+
+    #[tokio::test]
+    async fn get_habitat_species_fails_if_the_server_returns_404() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let poke_api_client = PokeApiClient::new(mock_server.uri(), Duration::from_millis(200));
+
+        let habitat = Faker.fake::<String>();
+        Mock::given(path(format!("/pokemon-habitat/{}", &habitat)))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let species = poke_api_client.get_habitat_species(&habitat).await;
+
+        assert_err!(&species);
+        assert_eq!(species.unwrap_err(), PokeApiClientError::NotFound);
+    }
 }
@@ -0,0 +1,992 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, RwLock},
+    time::{Duration, Instant, SystemTime},
+};
+
+use futures::{stream, StreamExt};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::cache::{
+    disk::DiskNamespace,
+    entry::{CacheEntry, CacheTier},
+    memory::TtlCache,
+    stale::{Refreshes, StalePolicy, Staleness},
+};
+use crate::poke_api::client::{
+    Appearance, Conditional, PokeApiClient, PokeApiClientError, PokemonInfo, SpeciesRef, Validators,
+};
+
+/// Enough to keep the appearance, or evolution chain, of every species of
+/// the national dex.
+const DETAIL_CACHE_ENTRIES: usize = 1100;
+
+/// Species fetched from PokeAPI at once when listing them, or looking for
+/// a random one.
+const FETCH_CONCURRENCY: usize = 8;
+
+/// Species fetched at most to answer one listing filtered on the legendary
+/// flag, which the index does not carry.
+const LEGENDARY_SCAN_LIMIT: usize = 200;
+
+#[derive(Debug, Default)]
+pub struct SpeciesFilter {
+    pub generation: Option<String>,
+    pub habitat: Option<String>,
+    pub legendary: Option<bool>,
+}
+
+#[derive(Debug)]
+pub struct PokemonPage {
+    /// Total number of matching species, only known when no filter requires
+    /// fetching every species payload.
+    pub count: Option<usize>,
+    pub results: Vec<PokemonInfo>,
+    pub has_more: bool,
+}
+
+struct CachedIndex {
+    fetched_at: Instant,
+    species: Arc<Vec<SpeciesRef>>,
+}
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct CachedSpecies {
+    #[schema(value_type = Object)]
+    pub fetched_at: SystemTime,
+    pub info: PokemonInfo,
+    pub validators: Validators,
+}
+
+pub struct Pokedex {
+    client: Arc<PokeApiClient>,
+    index_ttl: Duration,
+    index: RwLock<Option<CachedIndex>>,
+    species_ttl: Duration,
+    species: SpeciesStore,
+    stale_policy: StalePolicy,
+    refreshes: Arc<Refreshes>,
+    /// Names PokeAPI recently answered 404 for.
+    not_found: TtlCache<()>,
+    appearances: TtlCache<Appearance>,
+    evolution_chains: TtlCache<Arc<Vec<String>>>,
+    legendary_scan_limit: usize,
+}
+
+/// Species payloads by name, in memory and optionally on disk, cheap to
+/// clone into background refreshes.
+#[derive(Clone)]
+struct SpeciesStore {
+    client: Arc<PokeApiClient>,
+    entries: Arc<RwLock<HashMap<String, CachedSpecies>>>,
+    disk_cache: Option<Arc<DiskNamespace>>,
+}
+
+impl Pokedex {
+    pub fn new(client: PokeApiClient, index_ttl: Duration, species_ttl: Duration) -> Self {
+        let client = Arc::new(client);
+        Self {
+            client: client.clone(),
+            index_ttl,
+            index: RwLock::new(None),
+            species_ttl,
+            species: SpeciesStore {
+                client,
+                entries: Arc::new(RwLock::new(HashMap::new())),
+                disk_cache: None,
+            },
+            stale_policy: StalePolicy::default(),
+            refreshes: Arc::new(Refreshes::default()),
+            not_found: TtlCache::new(Duration::ZERO, 0),
+            appearances: TtlCache::new(species_ttl, DETAIL_CACHE_ENTRIES),
+            evolution_chains: TtlCache::new(species_ttl, DETAIL_CACHE_ENTRIES),
+            legendary_scan_limit: LEGENDARY_SCAN_LIMIT,
+        }
+    }
+
+    /// Remembers unknown names for `ttl`, so that repeated lookups of the
+    /// same garbage do not each reach PokeAPI. Forgotten whenever the species
+    /// index is refreshed, as it may have gained new species.
+    pub fn with_negative_cache(mut self, ttl: Duration, max_entries: usize) -> Self {
+        self.not_found = TtlCache::new(ttl, max_entries);
+        self
+    }
+
+    /// Keeps species payloads, and their validators, across restarts.
+    pub fn with_disk_cache(mut self, disk_cache: DiskNamespace) -> Self {
+        self.species.disk_cache = Some(Arc::new(disk_cache));
+        self
+    }
+
+    pub fn with_stale_policy(mut self, stale_policy: StalePolicy) -> Self {
+        self.stale_policy = stale_policy;
+        self
+    }
+
+    /// Species fetched at most to answer one listing filtered on the
+    /// legendary flag; pages past them are refused as bad requests.
+    pub fn with_legendary_scan_limit(mut self, legendary_scan_limit: usize) -> Self {
+        self.legendary_scan_limit = legendary_scan_limit;
+        self
+    }
+
+    pub async fn get_pokemon_info(&self, name: &str) -> Result<PokemonInfo, PokeApiClientError> {
+        Ok(self.lookup(name).await?.0)
+    }
+
+    /// A species, cached for the configured ttl. Expired entries are
+    /// revalidated against PokeAPI, which answers 304 when they are current,
+    /// and may still be served according to the stale policy.
+    pub async fn lookup(&self, name: &str) -> Result<(PokemonInfo, Staleness), PokeApiClientError> {
+        let name = name.to_lowercase();
+        let cached = match self.species.get(&name).await {
+            Some(cached) => cached,
+            None if self.not_found.get_with_age(&name).is_some() => {
+                return Err(PokeApiClientError::NotFound)
+            }
+            None => {
+                return match self.species.refresh(&name, None).await {
+                    Err(PokeApiClientError::NotFound) => {
+                        self.not_found.insert(name, ());
+                        Err(PokeApiClientError::NotFound)
+                    }
+                    result => result.map(|info| (info, Staleness::Fresh)),
+                }
+            }
+        };
+        let age = cached.fetched_at.elapsed().unwrap_or_default();
+        let expired_for = match age.checked_sub(self.species_ttl) {
+            None => return Ok((cached.info, Staleness::Fresh)),
+            Some(expired_for) => expired_for,
+        };
+        if expired_for < self.stale_policy.while_revalidate {
+            if self.refreshes.start(&name) {
+                let species = self.species.clone();
+                let refreshes = self.refreshes.clone();
+                let stale = cached.clone();
+                tokio::spawn(async move {
+                    let _ = species.refresh(&name, Some(stale)).await;
+                    refreshes.finish(&name);
+                });
+            }
+            return Ok((cached.info, Staleness::Stale));
+        }
+        match self.species.refresh(&name, Some(cached.clone())).await {
+            Err(PokeApiClientError::InternalError) if expired_for < self.stale_policy.if_error => {
+                Ok((cached.info, Staleness::RevalidationFailed))
+            }
+            result => result.map(|info| (info, Staleness::Fresh)),
+        }
+    }
+
+    /// Sprite and types of a species, cached as long as species payloads.
+    pub async fn appearance(
+        &self,
+        pokemon_info: &PokemonInfo,
+    ) -> Result<Appearance, PokeApiClientError> {
+        let name = pokemon_info.name.to_lowercase();
+        if let Some((appearance, _)) = self
+            .appearances
+            .get_with_age(&name)
+            .filter(|(_, age)| *age < self.appearances.ttl())
+        {
+            return Ok(appearance);
+        }
+        let id = pokemon_info.id.map(|id| id.to_string());
+        let appearance = self
+            .client
+            .get_appearance(id.as_deref().unwrap_or(&name))
+            .await?;
+        self.appearances.insert(name, appearance.clone());
+        Ok(appearance)
+    }
+
+    /// Species names of an evolution chain, cached as long as species
+    /// payloads.
+    pub async fn evolution_chain(&self, id: u32) -> Result<Arc<Vec<String>>, PokeApiClientError> {
+        let key = id.to_string();
+        if let Some((chain, _)) = self
+            .evolution_chains
+            .get_with_age(&key)
+            .filter(|(_, age)| *age < self.evolution_chains.ttl())
+        {
+            return Ok(chain);
+        }
+        let chain = Arc::new(self.client.get_evolution_chain(id).await?);
+        self.evolution_chains.insert(key, chain.clone());
+        Ok(chain)
+    }
+
+    /// Cached species, in memory and on disk, by lowercase name.
+    pub async fn cache_entries(&self) -> Vec<CacheEntry> {
+        let mut entries = self
+            .species
+            .entries
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, cached)| {
+                CacheEntry::new(
+                    name.clone(),
+                    CacheTier::Memory,
+                    cached.fetched_at.elapsed().unwrap_or_default(),
+                    self.species_ttl,
+                )
+            })
+            .collect::<Vec<CacheEntry>>();
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+        if let Some(disk_cache) = &self.species.disk_cache {
+            entries.extend(disk_cache.entries().await.unwrap_or_default());
+        }
+        entries
+    }
+
+    pub fn not_found_entries(&self) -> Vec<CacheEntry> {
+        self.not_found.entries()
+    }
+
+    /// The negative cache entry for `name`, if it is still remembered as
+    /// unknown.
+    pub fn not_found_entry(&self, name: &str) -> Option<CacheEntry> {
+        let name = name.to_lowercase();
+        self.not_found
+            .entries()
+            .into_iter()
+            .find(|entry| entry.key == name)
+    }
+
+    pub async fn cached_entry(&self, name: &str) -> Option<CachedSpecies> {
+        self.species.get(&name.to_lowercase()).await
+    }
+
+    /// Forgets everything cached about `name`, found or not, and about the
+    /// species it names under its other key: entries are keyed by whatever
+    /// was asked for, name or dex number. The species is fetched when not
+    /// cached, and returned when found, so that its translations can be
+    /// purged too.
+    pub async fn purge(&self, name: &str) -> (usize, Option<PokemonInfo>) {
+        let name = name.to_lowercase();
+        let pokemon_info = match self.cached_entry(&name).await {
+            Some(cached) => Some(cached.info),
+            None => self.get_pokemon_info(&name).await.ok(),
+        };
+        let mut keys = vec![name];
+        if let Some(pokemon_info) = &pokemon_info {
+            keys.push(pokemon_info.name.to_lowercase());
+            keys.extend(pokemon_info.id.map(|id| id.to_string()));
+        }
+        let purged = self
+            .purge_matching(move |key| keys.iter().any(|name| name == key))
+            .await;
+        (purged, pokemon_info)
+    }
+
+    pub async fn purge_all(&self) -> usize {
+        self.purge_matching(|_| true).await
+    }
+
+    async fn purge_matching(
+        &self,
+        matches: impl Fn(&str) -> bool + Clone + Send + 'static,
+    ) -> usize {
+        let mut purged = self.not_found.remove_matching(&matches);
+        self.appearances.remove_matching(&matches);
+        {
+            let mut entries = self.species.entries.write().unwrap();
+            let before = entries.len();
+            entries.retain(|name, _| !matches(name));
+            purged += before - entries.len();
+        }
+        if let Some(disk_cache) = &self.species.disk_cache {
+            purged += disk_cache
+                .remove_matching(matches)
+                .await
+                .unwrap_or_default();
+        }
+        purged
+    }
+
+    /// Every species of the national dex sorted by id, refreshed from PokeAPI
+    /// once it is older than the configured ttl.
+    pub async fn species_index(&self) -> Result<Arc<Vec<SpeciesRef>>, PokeApiClientError> {
+        if let Some(cached) = self.index.read().unwrap().as_ref() {
+            if cached.fetched_at.elapsed() < self.index_ttl {
+                return Ok(cached.species.clone());
+            }
+        }
+        let species = Arc::new(self.client.get_species_index().await?);
+        *self.index.write().unwrap() = Some(CachedIndex {
+            fetched_at: Instant::now(),
+            species: species.clone(),
+        });
+        self.not_found.clear();
+        Ok(species)
+    }
+
+    /// Species matching the generation and habitat filters, in dex order.
+    /// The legendary flag is only known from the species payload, so it is
+    /// not applied here.
+    pub async fn find_species(
+        &self,
+        filter: &SpeciesFilter,
+    ) -> Result<Vec<SpeciesRef>, PokeApiClientError> {
+        let mut species = self.species_index().await?.to_vec();
+        if let Some(generation) = &filter.generation {
+            let members = self
+                .client
+                .get_generation_species(&generation.to_lowercase())
+                .await
+                .map_err(|error| unknown_filter_value(error, "generation", generation))?;
+            retain_members(&mut species, &members);
+        }
+        if let Some(habitat) = &filter.habitat {
+            let members = self
+                .client
+                .get_habitat_species(&habitat.to_lowercase())
+                .await
+                .map_err(|error| unknown_filter_value(error, "habitat", habitat))?;
+            retain_members(&mut species, &members);
+        }
+        Ok(species)
+    }
+
+    /// A page of the species matching the filter. The legendary flag is
+    /// only known from species details, so filtering on it fetches them in
+    /// order until the page is full, and fails when that takes more than the
+    /// legendary scan limit.
+    pub async fn list(
+        &self,
+        filter: &SpeciesFilter,
+        offset: usize,
+        limit: usize,
+    ) -> Result<PokemonPage, PokeApiClientError> {
+        let candidates = self.find_species(filter).await?;
+        match filter.legendary {
+            None => {
+                let count = candidates.len();
+                let results = self
+                    .fetch_all(candidates.iter().skip(offset).take(limit))
+                    .await?;
+                Ok(PokemonPage {
+                    count: Some(count),
+                    results,
+                    has_more: offset + limit < count,
+                })
+            }
+            Some(is_legendary) => {
+                let mut skipped = 0;
+                let mut results = Vec::new();
+                let mut has_more = false;
+                for (i, chunk) in candidates.chunks(limit.max(1)).enumerate() {
+                    if i * limit.max(1) + chunk.len() > self.legendary_scan_limit {
+                        return Err(PokeApiClientError::BadRequest {
+                            message: format!(
+                                "legendary filter scans at most {} species, narrow it with generation or habitat",
+                                self.legendary_scan_limit
+                            ),
+                        });
+                    }
+                    let infos = self.fetch_all(chunk.iter()).await?;
+                    for info in infos
+                        .into_iter()
+                        .filter(|info| info.is_legendary == is_legendary)
+                    {
+                        if skipped < offset {
+                            skipped += 1;
+                        } else if results.len() < limit {
+                            results.push(info);
+                        } else {
+                            has_more = true;
+                            break;
+                        }
+                    }
+                    if has_more {
+                        break;
+                    }
+                }
+                Ok(PokemonPage {
+                    count: None,
+                    results,
+                    has_more,
+                })
+            }
+        }
+    }
+
+    /// A random species matching the filter. When filtering on the legendary
+    /// flag, candidates are fetched in random order, a batch at a time, until
+    /// one matches. Candidates that fail to load are skipped.
+    pub async fn random(&self, filter: &SpeciesFilter) -> Result<PokemonInfo, PokeApiClientError> {
+        let mut candidates = self.find_species(filter).await?;
+        candidates.shuffle(&mut rand::thread_rng());
+        let batch_size = match filter.legendary {
+            Some(_) => FETCH_CONCURRENCY,
+            None => 1,
+        };
+        let mut first_error = None;
+        let mut any_loaded = false;
+        for batch in candidates.chunks(batch_size) {
+            let results = stream::iter(batch)
+                .map(|species| self.get_pokemon_info(&species.name))
+                .buffer_unordered(FETCH_CONCURRENCY)
+                .collect::<Vec<Result<PokemonInfo, PokeApiClientError>>>()
+                .await;
+            for result in results {
+                match result {
+                    Ok(info) => {
+                        any_loaded = true;
+                        if filter
+                            .legendary
+                            .is_none_or(|legendary| legendary == info.is_legendary)
+                        {
+                            return Ok(info);
+                        }
+                    }
+                    Err(error) => {
+                        first_error.get_or_insert(error);
+                    }
+                }
+            }
+        }
+        match first_error {
+            Some(error) if !any_loaded => Err(error),
+            _ => Err(PokeApiClientError::NotFound),
+        }
+    }
+
+    /// The Pokémon of the given day, counted in days since the unix epoch.
+    /// The pick only depends on the seed, the day and the species index.
+    pub async fn daily(&self, seed: u64, day: u64) -> Result<PokemonInfo, PokeApiClientError> {
+        let index = self.species_index().await?;
+        if index.is_empty() {
+            return Err(PokeApiClientError::NotFound);
+        }
+        let species = &index[daily_pick(seed, day, index.len())];
+        self.get_pokemon_info(&species.name).await
+    }
+
+    /// Fetches species, at most `FETCH_CONCURRENCY` at a time, in the order
+    /// given. Species that fail are left out, unless all of them do.
+    async fn fetch_all<'a>(
+        &self,
+        species: impl Iterator<Item = &'a SpeciesRef>,
+    ) -> Result<Vec<PokemonInfo>, PokeApiClientError> {
+        let results = stream::iter(species)
+            .map(|species| self.get_pokemon_info(&species.name))
+            .buffered(FETCH_CONCURRENCY)
+            .collect::<Vec<Result<PokemonInfo, PokeApiClientError>>>()
+            .await;
+        let mut infos = Vec::new();
+        let mut first_error = None;
+        for result in results {
+            match result {
+                Ok(info) => infos.push(info),
+                Err(error) => {
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+        match first_error {
+            Some(error) if infos.is_empty() => Err(error),
+            _ => Ok(infos),
+        }
+    }
+}
+
+impl SpeciesStore {
+    async fn get(&self, name: &str) -> Option<CachedSpecies> {
+        if let Some(cached) = self.entries.read().unwrap().get(name) {
+            return Some(cached.clone());
+        }
+        let cached = self.disk_cache.as_ref()?.get::<CachedSpecies>(name).await?;
+        self.entries
+            .write()
+            .unwrap()
+            .insert(String::from(name), cached.clone());
+        Some(cached)
+    }
+
+    /// Fetches the species from PokeAPI, sending the validators of `cached`
+    /// so that an unchanged species costs no download.
+    async fn refresh(
+        &self,
+        name: &str,
+        cached: Option<CachedSpecies>,
+    ) -> Result<PokemonInfo, PokeApiClientError> {
+        let validators = cached
+            .as_ref()
+            .map(|cached| cached.validators.clone())
+            .unwrap_or_default();
+        let species = match self
+            .client
+            .get_pokemon_info_if_modified(name, &validators)
+            .await?
+        {
+            Conditional::Modified(info, validators) => CachedSpecies {
+                fetched_at: SystemTime::now(),
+                info,
+                validators,
+            },
+            Conditional::NotModified => CachedSpecies {
+                fetched_at: SystemTime::now(),
+                ..cached.ok_or(PokeApiClientError::InternalError)?
+            },
+        };
+        if let Some(disk_cache) = &self.disk_cache {
+            disk_cache.insert(name, species.clone()).await;
+        }
+        let info = species.info.clone();
+        self.entries
+            .write()
+            .unwrap()
+            .insert(String::from(name), species);
+        Ok(info)
+    }
+}
+
+/// Dialect a species description is translated into: Yoda for cave
+/// dwellers and legends, Shakespeare for everyone else.
+pub fn dialect_for(pokemon_info: &PokemonInfo) -> &'static str {
+    if pokemon_info.habitat.as_deref() == Some("cave") || pokemon_info.is_legendary {
+        "yoda"
+    } else {
+        "shakespeare"
+    }
+}
+
+/// Maps a seed and a day to an index below `len` through splitmix64, which,
+/// unlike the std hashers, is guaranteed to be stable across builds.
+fn daily_pick(seed: u64, day: u64, len: usize) -> usize {
+    (splitmix64(seed ^ splitmix64(day)) % len as u64) as usize
+}
+
+fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn retain_members(species: &mut Vec<SpeciesRef>, members: &[SpeciesRef]) {
+    let ids = members
+        .iter()
+        .map(|member| member.id)
+        .collect::<HashSet<u32>>();
+    species.retain(|species| ids.contains(&species.id));
+}
+
+fn unknown_filter_value(error: PokeApiClientError, kind: &str, value: &str) -> PokeApiClientError {
+    match error {
+        PokeApiClientError::NotFound => PokeApiClientError::BadRequest {
+            message: format!("unknown {} '{}'", kind, value),
+        },
+        error => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use claim::{assert_err, assert_ok};
+    use serde_json::{json, Value};
+    use wiremock::{
+        matchers::{header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use crate::{
+        cache::stale::{StalePolicy, Staleness},
+        poke_api::client::{PokeApiClient, PokeApiClientError},
+        pokedex::service::{daily_pick, Pokedex, SpeciesFilter},
+    };
+
+    fn species_json(name: &str, is_legendary: bool) -> Value {
+        json!({"flavor_text_entries":[{"flavor_text":"A description.","language":{"name":"en","url":"https://pokeapi.co/api/v2/language/9/"},"version":{"name":"red","url":"https://pokeapi.co/api/v2/version/1/"}}],"habitat":{"name":"rare","url":"https://pokeapi.co/api/v2/pokemon-habitat/5/"},"is_legendary":is_legendary,"name":name})
+    }
+
+    async fn mount_dex(mock_server: &MockServer, dex: &[(&str, bool)]) {
+        let results = dex
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| json!({"name": name, "url": format!("https://pokeapi.co/api/v2/pokemon-species/{}/", i + 1)}))
+            .collect::<Vec<Value>>();
+        Mock::given(path("/pokemon-species"))
+            .and(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({"count": dex.len(), "results": results})),
+            )
+            .mount(mock_server)
+            .await;
+        for (name, is_legendary) in dex {
+            Mock::given(path(format!("/pokemon-species/{}", name)))
+                .and(method("GET"))
+                .respond_with(
+                    ResponseTemplate::new(200).set_body_json(species_json(name, *is_legendary)),
+                )
+                .mount(mock_server)
+                .await;
+        }
+    }
+
+    #[tokio::test]
+    async fn list_returns_a_page_of_the_index() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        mount_dex(
+            &mock_server,
+            &[
+                ("bulbasaur", false),
+                ("ivysaur", false),
+                ("venusaur", false),
+            ],
+        )
+        .await;
+        let pokedex = Pokedex::new(
+            PokeApiClient::new(mock_server.uri(), Duration::from_millis(200)),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        );
+
+        // Act
+        let page = pokedex.list(&SpeciesFilter::default(), 1, 1).await;
+
+        assert_ok!(&page);
+        let page = page.unwrap();
+        assert_eq!(page.count, Some(3));
+        assert!(page.has_more);
+        assert_eq!(page.results.len(), 1);
+        assert_eq!(page.results[0].name, "ivysaur");
+    }
+
+    #[tokio::test]
+    async fn list_leaves_out_species_that_fail() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/pokemon-species/ivysaur"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&mock_server)
+            .await;
+        mount_dex(
+            &mock_server,
+            &[
+                ("bulbasaur", false),
+                ("ivysaur", false),
+                ("venusaur", false),
+            ],
+        )
+        .await;
+        let pokedex = Pokedex::new(
+            PokeApiClient::new(mock_server.uri(), Duration::from_millis(200)),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        );
+
+        // Act
+        let page = pokedex.list(&SpeciesFilter::default(), 0, 3).await;
+
+        assert_ok!(&page);
+        let names = page
+            .unwrap()
+            .results
+            .into_iter()
+            .map(|info| info.name)
+            .collect::<Vec<String>>();
+        assert_eq!(names, vec!["bulbasaur", "venusaur"]);
+    }
+
+    #[tokio::test]
+    async fn list_applies_the_legendary_filter_before_paginating() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        mount_dex(
+            &mock_server,
+            &[
+                ("articuno", true),
+                ("dratini", false),
+                ("zapdos", true),
+                ("moltres", true),
+            ],
+        )
+        .await;
+        let pokedex = Pokedex::new(
+            PokeApiClient::new(mock_server.uri(), Duration::from_millis(200)),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        );
+        let filter = SpeciesFilter {
+            legendary: Some(true),
+            ..SpeciesFilter::default()
+        };
+
+        // Act
+        let page = pokedex.list(&filter, 1, 1).await;
+
+        assert_ok!(&page);
+        let page = page.unwrap();
+        assert_eq!(page.count, None);
+        assert!(page.has_more);
+        assert_eq!(page.results.len(), 1);
+        assert_eq!(page.results[0].name, "zapdos");
+    }
+
+    #[tokio::test]
+    async fn list_refuses_legendary_pages_past_the_scan_limit() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        mount_dex(
+            &mock_server,
+            &[
+                ("bulbasaur", false),
+                ("ivysaur", false),
+                ("venusaur", false),
+                ("articuno", true),
+            ],
+        )
+        .await;
+        let pokedex = Pokedex::new(
+            PokeApiClient::new(mock_server.uri(), Duration::from_millis(200)),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        )
+        .with_legendary_scan_limit(2);
+        let filter = SpeciesFilter {
+            legendary: Some(true),
+            ..SpeciesFilter::default()
+        };
+
+        // Act
+        let page = pokedex.list(&filter, 0, 1).await;
+
+        // Assert
+        assert!(matches!(page, Err(PokeApiClientError::BadRequest { .. })));
+        let fetched = mock_server
+            .received_requests()
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|request| request.url.path().starts_with("/pokemon-species/"))
+            .count();
+        assert_eq!(fetched, 2);
+    }
+
+    #[tokio::test]
+    async fn random_only_returns_species_matching_the_filter() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        mount_dex(
+            &mock_server,
+            &[("dratini", false), ("mewtwo", true), ("pidgey", false)],
+        )
+        .await;
+        let pokedex = Pokedex::new(
+            PokeApiClient::new(mock_server.uri(), Duration::from_millis(200)),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        );
+        let filter = SpeciesFilter {
+            legendary: Some(true),
+            ..SpeciesFilter::default()
+        };
+
+        // Act
+        let info = pokedex.random(&filter).await;
+
+        assert_ok!(&info);
+        assert_eq!(info.unwrap().name, "mewtwo");
+    }
+
+    #[tokio::test]
+    async fn random_skips_failing_candidates_and_accepts_species_without_habitat() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/pokemon-species/missingno"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&mock_server)
+            .await;
+        let mut dialga = species_json("dialga", true);
+        dialga.as_object_mut().unwrap().remove("habitat");
+        Mock::given(path("/pokemon-species/dialga"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(dialga))
+            .mount(&mock_server)
+            .await;
+        mount_dex(
+            &mock_server,
+            &[("missingno", true), ("pidgey", false), ("dialga", true)],
+        )
+        .await;
+        let pokedex = Pokedex::new(
+            PokeApiClient::new(mock_server.uri(), Duration::from_millis(200)),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        );
+        let filter = SpeciesFilter {
+            legendary: Some(true),
+            ..SpeciesFilter::default()
+        };
+
+        // Act
+        let info = pokedex.random(&filter).await;
+
+        assert_ok!(&info);
+        let info = info.unwrap();
+        assert_eq!(info.name, "dialga");
+        assert_eq!(info.habitat, None);
+    }
+
+    #[tokio::test]
+    async fn get_pokemon_info_revalidates_expired_species() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/pokemon-species/mewtwo"))
+            .and(method("GET"))
+            .and(header("If-None-Match", "\"v1\""))
+            .respond_with(ResponseTemplate::new(304))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/pokemon-species/mewtwo"))
+            .and(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"v1\"")
+                    .set_body_json(species_json("mewtwo", true)),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let pokedex = Pokedex::new(
+            PokeApiClient::new(mock_server.uri(), Duration::from_millis(200)),
+            Duration::from_secs(60),
+            Duration::ZERO,
+        );
+
+        // Act
+        let fetched = pokedex.get_pokemon_info("Mewtwo").await;
+        let revalidated = pokedex.get_pokemon_info("mewtwo").await;
+
+        assert_ok!(&fetched);
+        assert_ok!(&revalidated);
+        assert_eq!(revalidated.unwrap().name, "mewtwo");
+    }
+
+    async fn mount_flaky_species(mock_server: &MockServer) {
+        Mock::given(path("/pokemon-species/mewtwo"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(species_json("mewtwo", true)))
+            .up_to_n_times(1)
+            .mount(mock_server)
+            .await;
+        Mock::given(path("/pokemon-species/mewtwo"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(mock_server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn lookup_serves_expired_species_while_revalidating() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        mount_flaky_species(&mock_server).await;
+        let pokedex = Pokedex::new(
+            PokeApiClient::new(mock_server.uri(), Duration::from_millis(200)),
+            Duration::from_secs(60),
+            Duration::ZERO,
+        )
+        .with_stale_policy(StalePolicy {
+            while_revalidate: Duration::from_secs(60),
+            if_error: Duration::ZERO,
+        });
+
+        // Act
+        let fetched = pokedex.lookup("mewtwo").await;
+        let stale = pokedex.lookup("mewtwo").await;
+
+        assert_eq!(fetched.unwrap().1, Staleness::Fresh);
+        let (info, staleness) = stale.unwrap();
+        assert_eq!(info.name, "mewtwo");
+        assert_eq!(staleness, Staleness::Stale);
+    }
+
+    #[tokio::test]
+    async fn lookup_serves_expired_species_when_revalidation_fails() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        mount_flaky_species(&mock_server).await;
+        let pokedex = Pokedex::new(
+            PokeApiClient::new(mock_server.uri(), Duration::from_millis(200)),
+            Duration::from_secs(60),
+            Duration::ZERO,
+        )
+        .with_stale_policy(StalePolicy {
+            while_revalidate: Duration::ZERO,
+            if_error: Duration::from_secs(60),
+        });
+
+        // Act
+        let fetched = pokedex.lookup("mewtwo").await;
+        let stale = pokedex.lookup("mewtwo").await;
+
+        assert_ok!(&fetched);
+        let (info, staleness) = stale.unwrap();
+        assert_eq!(info.name, "mewtwo");
+        assert_eq!(staleness, Staleness::RevalidationFailed);
+    }
+
+    #[tokio::test]
+    async fn lookup_remembers_unknown_names_until_the_index_refreshes() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        mount_dex(&mock_server, &[("bulbasaur", false)]).await;
+        Mock::given(path("/pokemon-species/missingno"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        let pokedex = Pokedex::new(
+            PokeApiClient::new(mock_server.uri(), Duration::from_millis(200)),
+            Duration::ZERO,
+            Duration::from_secs(60),
+        )
+        .with_negative_cache(Duration::from_secs(60), 10);
+
+        // Act
+        for _ in 0..3 {
+            assert_err!(pokedex.lookup("missingno").await);
+        }
+        assert_ok!(pokedex.species_index().await);
+        let after_refresh = pokedex.lookup("missingno").await;
+
+        assert_eq!(after_refresh.unwrap_err(), PokeApiClientError::NotFound);
+    }
+
+    #[test]
+    fn daily_pick_is_deterministic_and_depends_on_seed_and_day() {
+        let picks = (0..30)
+            .map(|day| daily_pick(42, day, 1000))
+            .collect::<Vec<usize>>();
+
+        assert_eq!(
+            picks,
+            (0..30)
+                .map(|day| daily_pick(42, day, 1000))
+                .collect::<Vec<usize>>()
+        );
+        assert!(picks.iter().all(|pick| *pick < 1000));
+        assert!(picks.windows(2).any(|pair| pair[0] != pair[1]));
+        assert_ne!(
+            picks,
+            (0..30)
+                .map(|day| daily_pick(7, day, 1000))
+                .collect::<Vec<usize>>()
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use std::{rc::Rc, str::FromStr};
+
+use actix_web::{
+    body::AnyBody,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    http::{
+        header::{self, HeaderValue},
+        StatusCode,
+    },
+    web, Error, HttpResponse,
+};
+use futures::future::{ready, LocalBoxFuture, Ready};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::error::PokeError;
+use crate::format::Format;
+use crate::translation::service::TranslationService;
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+/// Errors whose problem details tell when the translation budget allows a
+/// new attempt.
+const RETRY_AFTER_CODES: [&str; 2] = ["PE_RATE_LIMITED", "PE_QUOTA_EXHAUSTED"];
+
+/// How errors are rendered: as RFC 7807 problem details, or as the
+/// `{code, message}` objects served before them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    Problem,
+    Legacy,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "problem" => Ok(ErrorFormat::Problem),
+            "legacy" => Ok(ErrorFormat::Legacy),
+            _ => Err(format!("unknown error format '{}'", value)),
+        }
+    }
+}
+
+impl ErrorFormat {
+    /// The format the client asked for: problem details if it accepts
+    /// `application/problem+json`, the legacy format if it only names
+    /// `application/json`, and `default` otherwise.
+    pub fn negotiate(accept: Option<&str>, default: ErrorFormat) -> ErrorFormat {
+        let accept = match accept {
+            Some(accept) => accept,
+            None => return default,
+        };
+        let media_types = accept
+            .split(',')
+            .map(|media_type| media_type.split(';').next().unwrap_or("").trim())
+            .collect::<Vec<&str>>();
+        if media_types.contains(&PROBLEM_JSON) {
+            ErrorFormat::Problem
+        } else if media_types.contains(&"application/json") {
+            ErrorFormat::Legacy
+        } else {
+            default
+        }
+    }
+}
+
+/// RFC 7807 representation of a `PokeError`.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    /// Path of the request that failed.
+    pub instance: String,
+    /// Same `PE_*` code as the legacy format.
+    pub code: String,
+    pub request_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream: Option<String>,
+    /// Seconds until the translation budget allows a new attempt.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<u64>,
+}
+
+impl Problem {
+    pub fn new(error: &PokeError, type_base: &str, instance: String, request_id: String) -> Self {
+        let status =
+            StatusCode::from_u16(error.status_code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        Problem {
+            problem_type: format!("{}{}", type_base, problem_slug(&error.code)),
+            title: String::from(status.canonical_reason().unwrap_or("Error")),
+            status: status.as_u16(),
+            detail: error.message.clone(),
+            instance,
+            code: error.code.clone(),
+            request_id,
+            upstream: error.upstream.map(String::from),
+            retry_after: None,
+        }
+    }
+}
+
+/// `PE_QUOTA_EXHAUSTED` becomes `quota-exhausted`.
+fn problem_slug(code: &str) -> String {
+    code.trim_start_matches("PE_")
+        .to_lowercase()
+        .replace('_', "-")
+}
+
+/// Middleware rendering `PokeError`s in the format negotiated with the
+/// client, and refusing requests for an unknown `?format=`. Problem details
+/// are tagged with the request id, taken from the `X-Request-Id` header or
+/// generated.
+pub struct ProblemDetails {
+    default_format: ErrorFormat,
+    type_base: Rc<str>,
+}
+
+impl ProblemDetails {
+    /// Problem types are `type_base` followed by the slug of the error code.
+    pub fn new(default_format: ErrorFormat, type_base: &str) -> Self {
+        Self {
+            default_format,
+            type_base: Rc::from(type_base),
+        }
+    }
+}
+
+impl<S> Transform<S, ServiceRequest> for ProblemDetails
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<AnyBody>, Error = Error> + 'static,
+{
+    type Response = ServiceResponse<AnyBody>;
+    type Error = Error;
+    type Transform = ProblemDetailsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ProblemDetailsMiddleware {
+            service: Rc::new(service),
+            default_format: self.default_format,
+            type_base: self.type_base.clone(),
+        }))
+    }
+}
+
+pub struct ProblemDetailsMiddleware<S> {
+    service: Rc<S>,
+    default_format: ErrorFormat,
+    type_base: Rc<str>,
+}
+
+impl<S> Service<ServiceRequest> for ProblemDetailsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<AnyBody>, Error = Error> + 'static,
+{
+    type Response = ServiceResponse<AnyBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let header_value = |name| {
+            request
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from)
+        };
+        let format = ErrorFormat::negotiate(
+            header_value(header::ACCEPT.as_str()).as_deref(),
+            self.default_format,
+        );
+        let request_id = header_value(REQUEST_ID_HEADER)
+            .unwrap_or_else(|| format!("{:032x}", rand::random::<u128>()));
+        let type_base = self.type_base.clone();
+        let service = self.service.clone();
+        Box::pin(async move {
+            let response = match Format::requested(request.head()) {
+                Ok(_) => service.call(request).await?,
+                Err(error) => request.error_response(error),
+            };
+            if format == ErrorFormat::Legacy {
+                return Ok(response);
+            }
+            let error = match response
+                .response()
+                .error()
+                .and_then(|error| error.as_error::<PokeError>())
+            {
+                Some(error) => error.clone(),
+                None => return Ok(response),
+            };
+            let mut problem = Problem::new(
+                &error,
+                &type_base,
+                String::from(response.request().path()),
+                request_id.clone(),
+            );
+            // Only these are down to the translation budget; a full job
+            // queue, say, is not.
+            if RETRY_AFTER_CODES.contains(&problem.code.as_str()) {
+                problem.retry_after = response
+                    .request()
+                    .app_data::<web::Data<TranslationService>>()
+                    .and_then(|translation_service| translation_service.retry_after())
+                    .map(|retry_after| retry_after.as_secs().max(1));
+            }
+            // Only the body changes: headers set on the way out, such as
+            // deprecation notices or `Vary`, are kept.
+            let mut builder = HttpResponse::build(response.status());
+            for (name, value) in response.headers() {
+                if name != header::CONTENT_TYPE && name != header::CONTENT_LENGTH {
+                    builder.append_header((name.clone(), value.clone()));
+                }
+            }
+            builder
+                .content_type(PROBLEM_JSON)
+                .insert_header((REQUEST_ID_HEADER, request_id));
+            if let Some(retry_after) = problem.retry_after {
+                builder.insert_header((header::RETRY_AFTER, HeaderValue::from(retry_after)));
+            }
+            let body = serde_json::to_string(&problem).unwrap();
+            Ok(response.into_response(builder.body(body)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{get, http::StatusCode, middleware::DefaultHeaders, test, App};
+    use serde_json::Value;
+
+    use crate::error::PokeError;
+    use crate::problem::{ErrorFormat, ProblemDetails};
+
+    #[get("/pokemon/{name}")]
+    async fn missing() -> Result<String, PokeError> {
+        Err(PokeError::not_found(String::from("pokemon not found")))
+    }
+
+    #[test]
+    fn negotiate_honours_explicit_media_types() {
+        let negotiate = ErrorFormat::negotiate;
+
+        assert_eq!(
+            negotiate(Some("application/problem+json"), ErrorFormat::Legacy),
+            ErrorFormat::Problem
+        );
+        assert_eq!(
+            negotiate(Some("application/json; q=0.9"), ErrorFormat::Problem),
+            ErrorFormat::Legacy
+        );
+        assert_eq!(
+            negotiate(Some("*/*"), ErrorFormat::Problem),
+            ErrorFormat::Problem
+        );
+        assert_eq!(negotiate(None, ErrorFormat::Legacy), ErrorFormat::Legacy);
+    }
+
+    #[actix_rt::test]
+    async fn errors_are_rendered_as_problem_details_unless_legacy_is_asked_for() {
+        // Arrange
+        let app = test::init_service(
+            App::new()
+                .wrap(DefaultHeaders::new().header("Deprecation", "true"))
+                .wrap(ProblemDetails::new(ErrorFormat::Problem, "/problems/"))
+                .service(missing),
+        )
+        .await;
+
+        // Act
+        let problem_response = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/pokemon/missingno")
+                .insert_header(("X-Request-Id", "abc123"))
+                .to_request(),
+        )
+        .await;
+        let legacy_response = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/pokemon/missingno")
+                .insert_header(("Accept", "application/json"))
+                .to_request(),
+        )
+        .await;
+
+        // Assert
+        assert_eq!(problem_response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            problem_response.headers().get("content-type").unwrap(),
+            "application/problem+json"
+        );
+        assert_eq!(
+            problem_response.headers().get("deprecation").unwrap(),
+            "true"
+        );
+        let problem: Value = test::read_body_json(problem_response).await;
+        assert_eq!(problem["type"], "/problems/not-found");
+        assert_eq!(problem["title"], "Not Found");
+        assert_eq!(problem["status"], 404);
+        assert_eq!(problem["detail"], "pokemon not found");
+        assert_eq!(problem["instance"], "/pokemon/missingno");
+        assert_eq!(problem["code"], "PE_NOT_FOUND");
+        assert_eq!(problem["request_id"], "abc123");
+        let legacy: Value = test::read_body_json(legacy_response).await;
+        assert_eq!(legacy["code"], "PE_NOT_FOUND");
+        assert_eq!(legacy["message"], "pokemon not found");
+        assert!(legacy.get("type").is_none());
+    }
+}
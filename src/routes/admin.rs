@@ -0,0 +1,633 @@
+use actix_web::{delete, get, http::header, post, web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::cache::entry::CacheEntry;
+use crate::configuration::Settings;
+use crate::error::PokeError;
+use crate::jobs::pretranslate::Pretranslation;
+use crate::pokedex::service::{CachedSpecies, Pokedex};
+use crate::routes::pokemon::translate_pokemon_info;
+use crate::translation::service::TranslationService;
+use crate::translation::translator::Translation;
+
+const SPECIES_CACHE: &str = "species";
+const NOT_FOUND_CACHE: &str = "not-found";
+const TRANSLATIONS_CACHE: &str = "translations";
+/// Most species a single warm request may ask for.
+const MAX_WARM_NAMES: usize = 100;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct CacheParams {
+    cache: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct EntryParams {
+    cache: String,
+    key: String,
+}
+
+/// Exactly one of the selectors must be given.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PurgeParams {
+    pokemon: Option<String>,
+    dialect: Option<String>,
+    #[serde(default)]
+    all: bool,
+}
+
+/// A cached value, as stored: its shape depends on the cache.
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum CachedEntry {
+    Species(CachedSpecies),
+    Translation(Translation),
+    /// The negative cache stores no value, only when a name was found
+    /// unknown.
+    NotFound(CacheEntry),
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Purged {
+    species: usize,
+    translations: usize,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct WarmRequest {
+    names: Vec<String>,
+    /// Whether to translate the descriptions too, which spends quota.
+    #[serde(default)]
+    translate: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Warmed {
+    name: String,
+    /// `PE_OK`, or the code of the error met while warming.
+    code: String,
+}
+
+/// Checks the `Authorization: Bearer <token>` header against the configured
+/// admin token.
+pub fn authorize(request: &HttpRequest, settings: &Settings) -> Result<(), PokeError> {
+    let expected = settings
+        .admin_token
+        .as_ref()
+        .ok_or_else(|| PokeError::forbidden(String::from("admin api is disabled")))?;
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(PokeError::unauthorized)?;
+    if constant_time_eq(provided.as_bytes(), expected.expose().as_bytes()) {
+        Ok(())
+    } else {
+        Err(PokeError::unauthorized())
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/quota",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, description = "Remaining budget of the rate limited providers", body = [ProviderQuota]),
+        (status = 401, description = "PE_UNAUTHORIZED: missing or invalid admin token", body = PokeError),
+        (status = 403, description = "PE_FORBIDDEN: admin api disabled", body = PokeError),
+    )
+)]
+#[get("/admin/quota")]
+pub async fn get_quota(
+    request: HttpRequest,
+    translation_service: web::Data<TranslationService>,
+    settings: web::Data<Settings>,
+) -> Result<HttpResponse, PokeError> {
+    authorize(&request, &settings)?;
+    Ok(HttpResponse::Ok().json(translation_service.quotas()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/jobs/pretranslation",
+    tag = "admin",
+    security(("admin_token" = [])),
+    responses(
+        (status = 200, description = "Progress of the pre-translation job", body = PretranslationProgress),
+        (status = 401, description = "PE_UNAUTHORIZED: missing or invalid admin token", body = PokeError),
+        (status = 403, description = "PE_FORBIDDEN: admin api disabled", body = PokeError),
+    )
+)]
+#[get("/admin/jobs/pretranslation")]
+pub async fn get_pretranslation(
+    request: HttpRequest,
+    pretranslation: web::Data<Pretranslation>,
+    settings: web::Data<Settings>,
+) -> Result<HttpResponse, PokeError> {
+    authorize(&request, &settings)?;
+    Ok(HttpResponse::Ok().json(pretranslation.progress()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/cache/{cache}",
+    tag = "admin",
+    security(("admin_token" = [])),
+    params(CacheParams),
+    responses(
+        (status = 200, description = "Entries of the cache", body = [CacheEntry]),
+        (status = 401, description = "PE_UNAUTHORIZED: missing or invalid admin token", body = PokeError),
+        (status = 403, description = "PE_FORBIDDEN: admin api disabled", body = PokeError),
+        (status = 404, description = "PE_NOT_FOUND: unknown cache", body = PokeError),
+    )
+)]
+#[get("/admin/cache/{cache}")]
+pub async fn list_cache(
+    request: HttpRequest,
+    params: web::Path<CacheParams>,
+    pokedex: web::Data<Pokedex>,
+    translation_service: web::Data<TranslationService>,
+    settings: web::Data<Settings>,
+) -> Result<HttpResponse, PokeError> {
+    authorize(&request, &settings)?;
+    let entries: Vec<CacheEntry> = match params.cache.as_str() {
+        SPECIES_CACHE => pokedex.cache_entries().await,
+        NOT_FOUND_CACHE => pokedex.not_found_entries(),
+        TRANSLATIONS_CACHE => translation_service.cache_entries().await,
+        _ => return Err(unknown_cache(&params.cache)),
+    };
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/cache/{cache}/{key}",
+    tag = "admin",
+    security(("admin_token" = [])),
+    params(EntryParams),
+    responses(
+        (status = 200, description = "The cached value", body = CachedEntry),
+        (status = 401, description = "PE_UNAUTHORIZED: missing or invalid admin token", body = PokeError),
+        (status = 403, description = "PE_FORBIDDEN: admin api disabled", body = PokeError),
+        (status = 404, description = "PE_NOT_FOUND: unknown cache or key", body = PokeError),
+    )
+)]
+#[get("/admin/cache/{cache}/{key}")]
+pub async fn get_cache_entry(
+    request: HttpRequest,
+    params: web::Path<EntryParams>,
+    pokedex: web::Data<Pokedex>,
+    translation_service: web::Data<TranslationService>,
+    settings: web::Data<Settings>,
+) -> Result<HttpResponse, PokeError> {
+    authorize(&request, &settings)?;
+    let entry = match params.cache.as_str() {
+        SPECIES_CACHE => pokedex
+            .cached_entry(&params.key)
+            .await
+            .map(CachedEntry::Species),
+        NOT_FOUND_CACHE => pokedex
+            .not_found_entry(&params.key)
+            .map(CachedEntry::NotFound),
+        TRANSLATIONS_CACHE => translation_service
+            .cached_entry(&params.key)
+            .await
+            .map(CachedEntry::Translation),
+        _ => return Err(unknown_cache(&params.cache)),
+    };
+    entry
+        .map(|entry| HttpResponse::Ok().json(entry))
+        .ok_or_else(|| PokeError::not_found(format!("no cached entry '{}'", params.key)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/cache",
+    tag = "admin",
+    security(("admin_token" = [])),
+    params(PurgeParams),
+    responses(
+        (status = 200, description = "How many entries were purged", body = Purged),
+        (status = 400, description = "PE_BAD_REQUEST: not exactly one selector", body = PokeError),
+        (status = 401, description = "PE_UNAUTHORIZED: missing or invalid admin token", body = PokeError),
+        (status = 403, description = "PE_FORBIDDEN: admin api disabled", body = PokeError),
+    )
+)]
+#[delete("/admin/cache")]
+pub async fn purge_cache(
+    request: HttpRequest,
+    params: web::Query<PurgeParams>,
+    pokedex: web::Data<Pokedex>,
+    translation_service: web::Data<TranslationService>,
+    settings: web::Data<Settings>,
+) -> Result<HttpResponse, PokeError> {
+    authorize(&request, &settings)?;
+    let purged = match (&params.pokemon, &params.dialect, params.all) {
+        (Some(pokemon), None, false) => {
+            let (species, pokemon_info) = pokedex.purge(pokemon).await;
+            let translations = match pokemon_info {
+                Some(pokemon_info) => {
+                    translation_service
+                        .purge_text(&pokemon_info.description)
+                        .await
+                }
+                None => 0,
+            };
+            Purged {
+                species,
+                translations,
+            }
+        }
+        (None, Some(dialect), false) => Purged {
+            species: 0,
+            translations: translation_service
+                .purge_dialect(&dialect.to_lowercase())
+                .await,
+        },
+        (None, None, true) => Purged {
+            species: pokedex.purge_all().await,
+            translations: translation_service.purge_all().await,
+        },
+        _ => {
+            return Err(PokeError::bad_request(String::from(
+                "give exactly one of pokemon, dialect or all=true",
+            )))
+        }
+    };
+    Ok(HttpResponse::Ok().json(purged))
+}
+
+/// Fetches the given species one after the other, at most 100 of them,
+/// translating them if asked to, and reports how each went.
+#[utoipa::path(
+    post,
+    path = "/admin/cache/warm",
+    tag = "admin",
+    security(("admin_token" = [])),
+    request_body = WarmRequest,
+    responses(
+        (status = 200, description = "How warming each species went", body = [Warmed]),
+        (status = 400, description = "PE_BAD_REQUEST: malformed body, or more than 100 names", body = PokeError),
+        (status = 401, description = "PE_UNAUTHORIZED: missing or invalid admin token", body = PokeError),
+        (status = 403, description = "PE_FORBIDDEN: admin api disabled", body = PokeError),
+    )
+)]
+#[post("/admin/cache/warm")]
+pub async fn warm_cache(
+    request: HttpRequest,
+    body: web::Json<WarmRequest>,
+    pokedex: web::Data<Pokedex>,
+    translation_service: web::Data<TranslationService>,
+    settings: web::Data<Settings>,
+) -> Result<HttpResponse, PokeError> {
+    authorize(&request, &settings)?;
+    if body.names.len() > MAX_WARM_NAMES {
+        return Err(PokeError::bad_request(format!(
+            "names must list at most {} species",
+            MAX_WARM_NAMES
+        )));
+    }
+    let mut warmed = Vec::with_capacity(body.names.len());
+    for name in &body.names {
+        let result = match pokedex.get_pokemon_info(name).await {
+            Ok(pokemon_info) if body.translate => {
+                translate_pokemon_info(pokemon_info, &translation_service)
+                    .await
+                    .map(|_| ())
+            }
+            Ok(_) => Ok(()),
+            Err(error) => Err(PokeError::from(error)),
+        };
+        warmed.push(Warmed {
+            name: name.clone(),
+            code: result.map_or_else(|error| error.code, |_| String::from("PE_OK")),
+        });
+    }
+    Ok(HttpResponse::Ok().json(warmed))
+}
+
+fn unknown_cache(cache: &str) -> PokeError {
+    PokeError::not_found(format!(
+        "unknown cache '{}', expected one of {}, {} or {}",
+        cache, SPECIES_CACHE, NOT_FOUND_CACHE, TRANSLATIONS_CACHE
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc, time::Duration};
+
+    use actix_web::{
+        http::{header, StatusCode},
+        test,
+        web::Data,
+        App,
+    };
+    use claim::{assert_err, assert_ok};
+    use serde_json::{json, Value};
+    use wiremock::{
+        matchers::{any, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use crate::{
+        cache::memory::TtlCache,
+        configuration::{Secret, Settings},
+        poke_api::client::PokeApiClient,
+        pokedex::service::Pokedex,
+        routes::admin::{authorize, get_cache_entry, list_cache, purge_cache, warm_cache},
+        translation::{
+            chain::{HealthPolicy, TranslatorChain},
+            local::LocalTranslator,
+            registry::TranslatorRegistry,
+            service::TranslationService,
+        },
+    };
+
+    fn local_translation_service() -> TranslationService {
+        TranslationService::new(
+            TranslatorChain::new(
+                TranslatorRegistry::new(vec![Arc::new(LocalTranslator)], &HashMap::new()).unwrap(),
+                vec![String::from("local")],
+                HealthPolicy {
+                    failure_threshold: 3,
+                    cooldown: Duration::from_secs(60),
+                },
+                HashMap::new(),
+            )
+            .unwrap(),
+            TtlCache::new(Duration::from_secs(60), 10),
+        )
+    }
+
+    #[test]
+    fn authorize_requires_the_configured_bearer_token() {
+        let mut settings = Settings::from_vars(&|_| None);
+        settings.admin_token = Some(Secret::new(String::from("s3cr3t")));
+
+        let missing = test::TestRequest::default().to_http_request();
+        let wrong = test::TestRequest::default()
+            .insert_header((header::AUTHORIZATION, "Bearer guess"))
+            .to_http_request();
+        let right = test::TestRequest::default()
+            .insert_header((header::AUTHORIZATION, "Bearer s3cr3t"))
+            .to_http_request();
+
+        assert_err!(authorize(&missing, &settings));
+        assert_err!(authorize(&wrong, &settings));
+        assert_ok!(authorize(&right, &settings));
+    }
+
+    #[test]
+    fn authorize_fails_when_no_token_is_configured() {
+        let mut settings = Settings::from_vars(&|_| None);
+        settings.admin_token = None;
+        let request = test::TestRequest::default()
+            .insert_header((header::AUTHORIZATION, "Bearer "))
+            .to_http_request();
+
+        let error = authorize(&request, &settings).unwrap_err();
+
+        assert_eq!(error.code, "PE_FORBIDDEN");
+    }
+
+    #[actix_rt::test]
+    async fn warmed_translations_can_be_listed_and_purged_by_pokemon() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let species = json!({"flavor_text_entries":[{"flavor_text":"It was created by a scientist.","language":{"name":"en","url":"https://pokeapi.co/api/v2/language/9/"},"version":{"name":"red","url":"https://pokeapi.co/api/v2/version/1/"}}],"habitat":{"name":"rare","url":"https://pokeapi.co/api/v2/pokemon-habitat/5/"},"is_legendary":true,"name":"mewtwo"});
+        Mock::given(path("/pokemon-species/mewtwo"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(species))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let mut settings = Settings::from_vars(&|_| None);
+        settings.admin_token = Some(Secret::new(String::from("s3cr3t")));
+        let translation_service = local_translation_service();
+        let app = test::init_service(
+            App::new()
+                .service(list_cache)
+                .service(warm_cache)
+                .service(purge_cache)
+                .app_data(Data::new(Pokedex::new(
+                    PokeApiClient::new(mock_server.uri(), Duration::from_millis(200)),
+                    Duration::from_secs(60),
+                    Duration::from_secs(60),
+                )))
+                .app_data(Data::new(translation_service))
+                .app_data(Data::new(settings)),
+        )
+        .await;
+        let authorized = |request: test::TestRequest| {
+            request
+                .insert_header((header::AUTHORIZATION, "Bearer s3cr3t"))
+                .to_request()
+        };
+
+        // Act
+        let warmed: Value = test::read_response_json(
+            &app,
+            authorized(
+                test::TestRequest::post()
+                    .uri("/admin/cache/warm")
+                    .set_json(&json!({"names": ["Mewtwo", "0"], "translate": true})),
+            ),
+        )
+        .await;
+        let listed: Value = test::read_response_json(
+            &app,
+            authorized(test::TestRequest::get().uri("/admin/cache/translations")),
+        )
+        .await;
+        let purged: Value = test::read_response_json(
+            &app,
+            authorized(test::TestRequest::delete().uri("/admin/cache?pokemon=mewtwo")),
+        )
+        .await;
+        let remaining: Value = test::read_response_json(
+            &app,
+            authorized(test::TestRequest::get().uri("/admin/cache/translations")),
+        )
+        .await;
+
+        assert_eq!(warmed[0]["code"], "PE_OK");
+        assert_eq!(warmed[1]["code"], "PE_BAD_REQUEST");
+        assert_eq!(listed[0]["key"], "yoda:It was created by a scientist.");
+        assert_eq!(listed[0]["tier"], "memory");
+        assert_eq!(purged, json!({"species": 1, "translations": 1}));
+        assert_eq!(remaining, json!([]));
+    }
+
+    #[actix_rt::test]
+    async fn purging_by_pokemon_covers_every_key_of_the_species() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let species = json!({"flavor_text_entries":[{"flavor_text":"It was created by a scientist.","language":{"name":"en","url":"https://pokeapi.co/api/v2/language/9/"},"version":{"name":"red","url":"https://pokeapi.co/api/v2/version/1/"}}],"habitat":{"name":"rare","url":"https://pokeapi.co/api/v2/pokemon-habitat/5/"},"id":150,"is_legendary":true,"name":"mewtwo"});
+        for key in ["150", "mewtwo"] {
+            Mock::given(path(format!("/pokemon-species/{}", key)))
+                .and(method("GET"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(species.clone()))
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+        }
+        let mut settings = Settings::from_vars(&|_| None);
+        settings.admin_token = Some(Secret::new(String::from("s3cr3t")));
+        let app = test::init_service(
+            App::new()
+                .service(list_cache)
+                .service(warm_cache)
+                .service(purge_cache)
+                .app_data(Data::new(Pokedex::new(
+                    PokeApiClient::new(mock_server.uri(), Duration::from_millis(200)),
+                    Duration::from_secs(60),
+                    Duration::from_secs(60),
+                )))
+                .app_data(Data::new(local_translation_service()))
+                .app_data(Data::new(settings)),
+        )
+        .await;
+        let authorized = |request: test::TestRequest| {
+            request
+                .insert_header((header::AUTHORIZATION, "Bearer s3cr3t"))
+                .to_request()
+        };
+
+        // Act
+        let _: Value = test::read_response_json(
+            &app,
+            authorized(
+                test::TestRequest::post()
+                    .uri("/admin/cache/warm")
+                    .set_json(&json!({"names": ["150"], "translate": true})),
+            ),
+        )
+        .await;
+        let purged: Value = test::read_response_json(
+            &app,
+            authorized(test::TestRequest::delete().uri("/admin/cache?pokemon=mewtwo")),
+        )
+        .await;
+        let species: Value = test::read_response_json(
+            &app,
+            authorized(test::TestRequest::get().uri("/admin/cache/species")),
+        )
+        .await;
+
+        // Assert
+        assert_eq!(purged, json!({"species": 2, "translations": 1}));
+        assert_eq!(species, json!([]));
+    }
+
+    #[actix_rt::test]
+    async fn unknown_names_can_be_read_from_the_negative_cache() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/pokemon-species/missingno"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let mut settings = Settings::from_vars(&|_| None);
+        settings.admin_token = Some(Secret::new(String::from("s3cr3t")));
+        let app = test::init_service(
+            App::new()
+                .service(warm_cache)
+                .service(get_cache_entry)
+                .app_data(Data::new(
+                    Pokedex::new(
+                        PokeApiClient::new(mock_server.uri(), Duration::from_millis(200)),
+                        Duration::from_secs(60),
+                        Duration::from_secs(60),
+                    )
+                    .with_negative_cache(Duration::from_secs(60), 10),
+                ))
+                .app_data(Data::new(local_translation_service()))
+                .app_data(Data::new(settings)),
+        )
+        .await;
+        let authorized = |request: test::TestRequest| {
+            request
+                .insert_header((header::AUTHORIZATION, "Bearer s3cr3t"))
+                .to_request()
+        };
+
+        // Act
+        let _: Value = test::read_response_json(
+            &app,
+            authorized(
+                test::TestRequest::post()
+                    .uri("/admin/cache/warm")
+                    .set_json(&json!({"names": ["MissingNo"]})),
+            ),
+        )
+        .await;
+        let entry: Value = test::read_response_json(
+            &app,
+            authorized(test::TestRequest::get().uri("/admin/cache/not-found/missingno")),
+        )
+        .await;
+        let unknown = test::call_service(
+            &app,
+            authorized(test::TestRequest::get().uri("/admin/cache/not-found/mewtwo")),
+        )
+        .await;
+
+        // Assert
+        assert_eq!(entry["key"], "missingno");
+        assert_eq!(entry["tier"], "memory");
+        assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn warming_refuses_more_than_a_hundred_names() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(404))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+        let mut settings = Settings::from_vars(&|_| None);
+        settings.admin_token = Some(Secret::new(String::from("s3cr3t")));
+        let app = test::init_service(
+            App::new()
+                .service(warm_cache)
+                .app_data(Data::new(Pokedex::new(
+                    PokeApiClient::new(mock_server.uri(), Duration::from_millis(200)),
+                    Duration::from_secs(60),
+                    Duration::from_secs(60),
+                )))
+                .app_data(Data::new(local_translation_service()))
+                .app_data(Data::new(settings)),
+        )
+        .await;
+        let names = (1..=101).map(|id| id.to_string()).collect::<Vec<String>>();
+
+        // Act
+        let response = test::call_service(
+            &app,
+            test::TestRequest::post()
+                .uri("/admin/cache/warm")
+                .insert_header((header::AUTHORIZATION, "Bearer s3cr3t"))
+                .set_json(&json!({ "names": names }))
+                .to_request(),
+        )
+        .await;
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,270 @@
+use std::time::Duration;
+
+use actix_web::{
+    http::{
+        header::{
+            CacheControl, CacheDirective, ContentType, ETag, EntityTag, HttpDate, IfModifiedSince,
+            IfNoneMatch, LastModified, WARNING,
+        },
+        StatusCode,
+    },
+    HttpMessage, HttpRequest, HttpResponse, HttpResponseBuilder,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::cache::stale::Staleness;
+use crate::format::Format;
+
+/// How long clients and shared caches may reuse a response.
+pub enum Freshness {
+    MaxAge(Duration),
+    /// Reusable only after revalidating, e.g. a translation from a fallback
+    /// provider that should be replaced as soon as the preferred one is back.
+    Revalidate,
+}
+
+impl Freshness {
+    /// `max_age`, unless the response was built from expired entries, which
+    /// downstream caches should not keep.
+    pub fn unless_stale(max_age: Duration, staleness: Staleness) -> Self {
+        match staleness {
+            Staleness::Fresh => Freshness::MaxAge(max_age),
+            _ => Freshness::Revalidate,
+        }
+    }
+
+    fn cache_control(&self) -> CacheControl {
+        match self {
+            Freshness::MaxAge(max_age) => CacheControl(vec![
+                CacheDirective::Public,
+                CacheDirective::MaxAge(max_age.as_secs() as u32),
+            ]),
+            Freshness::Revalidate => CacheControl(vec![CacheDirective::NoCache]),
+        }
+    }
+}
+
+/// Adds a `Warning` header to responses built from expired cache entries.
+pub fn mark_staleness(response: &mut HttpResponseBuilder, staleness: Staleness) {
+    if let Some(warning) = staleness.warning() {
+        response.insert_header((WARNING, warning));
+    }
+}
+
+/// Strong entity tag of a body: the SHA-256 of its bytes.
+pub fn etag_for(body: &[u8]) -> EntityTag {
+    let digest = Sha256::digest(body);
+    EntityTag::strong(
+        digest
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>(),
+    )
+}
+
+/// Serializes `value` as the body of `response`, tagged with its ETag (one
+/// per requested format) and `last_modified` when known, or answers 304 Not
+/// Modified if the request already holds that version.
+pub fn conditional_json<T: Serialize>(
+    request: &HttpRequest,
+    response: HttpResponseBuilder,
+    value: &T,
+    last_modified: Option<HttpDate>,
+    freshness: Freshness,
+) -> HttpResponse {
+    let body = serde_json::to_vec(value).unwrap();
+    let etag = match Format::requested(request.head()) {
+        // Each representation of the body is a distinct version.
+        Ok(format) if format != Format::Json => {
+            EntityTag::strong(format!("{}-{}", etag_for(&body).tag(), format.name()))
+        }
+        _ => etag_for(&body),
+    };
+    conditional(
+        request,
+        response,
+        body,
+        etag,
+        last_modified,
+        ContentType(mime::APPLICATION_JSON),
+        freshness,
+    )
+}
+
+/// Same as `conditional_json`, for an HTML page.
+pub fn conditional_html(
+    request: &HttpRequest,
+    response: HttpResponseBuilder,
+    body: String,
+    freshness: Freshness,
+) -> HttpResponse {
+    let etag = etag_for(body.as_bytes());
+    conditional(
+        request,
+        response,
+        body.into_bytes(),
+        etag,
+        None,
+        ContentType(mime::TEXT_HTML_UTF_8),
+        freshness,
+    )
+}
+
+fn conditional(
+    request: &HttpRequest,
+    mut response: HttpResponseBuilder,
+    body: Vec<u8>,
+    etag: EntityTag,
+    last_modified: Option<HttpDate>,
+    content_type: ContentType,
+    freshness: Freshness,
+) -> HttpResponse {
+    let not_modified = match request.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+        // If-Modified-Since only counts when there is no If-None-Match.
+        None => match (last_modified, request.get_header::<IfModifiedSince>()) {
+            (Some(last_modified), Some(IfModifiedSince(since))) => last_modified <= since,
+            _ => false,
+        },
+    };
+    response
+        .insert_header(ETag(etag))
+        .insert_header(freshness.cache_control());
+    if let Some(last_modified) = last_modified {
+        response.insert_header(LastModified(last_modified));
+    }
+    if not_modified {
+        response.status(StatusCode::NOT_MODIFIED).finish()
+    } else {
+        response.insert_header(content_type).body(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use actix_web::{
+        http::{header, StatusCode},
+        test, HttpResponse,
+    };
+
+    use crate::routes::caching::{conditional_html, conditional_json, etag_for, Freshness};
+
+    #[test]
+    fn conditional_json_tags_the_body_and_sets_the_max_age() {
+        let request = test::TestRequest::default().to_http_request();
+
+        let response = conditional_json(
+            &request,
+            HttpResponse::Ok(),
+            &"pikachu",
+            None,
+            Freshness::MaxAge(Duration::from_secs(60)),
+        );
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(header::ETAG).unwrap(),
+            &etag_for(b"\"pikachu\"").to_string()
+        );
+        assert_eq!(
+            response.headers().get(header::CACHE_CONTROL).unwrap(),
+            "public, max-age=60"
+        );
+    }
+
+    #[test]
+    fn conditional_json_answers_304_when_the_etag_matches() {
+        let etag = etag_for(b"\"pikachu\"").to_string();
+        let matching = test::TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, format!("\"other\", {}", etag)))
+            .to_http_request();
+        let stale = test::TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, "\"other\""))
+            .to_http_request();
+
+        let not_modified = conditional_json(
+            &matching,
+            HttpResponse::Ok(),
+            &"pikachu",
+            None,
+            Freshness::Revalidate,
+        );
+        let modified = conditional_json(
+            &stale,
+            HttpResponse::Ok(),
+            &"pikachu",
+            None,
+            Freshness::Revalidate,
+        );
+
+        assert_eq!(not_modified.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(
+            not_modified.headers().get(header::CACHE_CONTROL).unwrap(),
+            "no-cache"
+        );
+        assert_eq!(modified.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn conditional_html_tags_the_page() {
+        let page = String::from("<h1>pikachu</h1>");
+        let etag = etag_for(page.as_bytes()).to_string();
+        let request = test::TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, etag.clone()))
+            .to_http_request();
+
+        let fresh = conditional_html(
+            &test::TestRequest::default().to_http_request(),
+            HttpResponse::Ok(),
+            page.clone(),
+            Freshness::MaxAge(Duration::from_secs(60)),
+        );
+        let not_modified = conditional_html(
+            &request,
+            HttpResponse::Ok(),
+            page,
+            Freshness::MaxAge(Duration::from_secs(60)),
+        );
+
+        assert_eq!(fresh.status(), StatusCode::OK);
+        assert_eq!(fresh.headers().get(header::ETAG).unwrap(), &etag);
+        assert_eq!(
+            fresh.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(not_modified.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[test]
+    fn conditional_json_answers_304_when_not_modified_since() {
+        let last_modified = "Wed, 21 Oct 2015 07:28:00 GMT";
+        let since = |date: &str| {
+            test::TestRequest::default()
+                .insert_header((header::IF_MODIFIED_SINCE, date))
+                .to_http_request()
+        };
+        let respond = |request| {
+            conditional_json(
+                &request,
+                HttpResponse::Ok(),
+                &"pikachu",
+                Some(last_modified.parse().unwrap()),
+                Freshness::Revalidate,
+            )
+        };
+
+        let not_modified = respond(since(last_modified));
+        let modified = respond(since("Tue, 20 Oct 2015 07:28:00 GMT"));
+
+        assert_eq!(not_modified.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(modified.status(), StatusCode::OK);
+        assert_eq!(
+            modified.headers().get(header::LAST_MODIFIED).unwrap(),
+            last_modified
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use actix_web::{http::header, HttpRequest, HttpResponse};
+use askama::Template;
+
+use crate::error::PokeError;
+use crate::poke_api::client::PokemonInfo;
+use crate::pokedex::service::{dialect_for, Pokedex};
+use crate::routes::caching::{conditional_html, Freshness};
+use crate::translation::service::TranslationService;
+
+/// Habitat badge of species PokeAPI assigns none.
+const UNKNOWN_HABITAT: &str = "unknown";
+
+/// Pokédex card of a species, for people rather than programs. Every value
+/// is escaped, the translated text in particular coming from a third party.
+#[derive(Template)]
+#[template(path = "card.html")]
+struct PokedexCard<'a> {
+    name: &'a str,
+    number: Option<u32>,
+    sprite: Option<&'a str>,
+    types: &'a [String],
+    habitat: &'a str,
+    is_legendary: bool,
+    is_mythical: bool,
+    original: &'a str,
+    translated: Option<&'a str>,
+    engine: Option<&'a str>,
+}
+
+/// Renders the card of `pokemon_info`, with an ETag and `Cache-Control` like
+/// its JSON representation. Neither the sprite and types nor the translation are
+/// essential, so failing to get them leaves them out rather than failing the
+/// page.
+pub async fn render(
+    request: &HttpRequest,
+    pokemon_info: &PokemonInfo,
+    freshness: Freshness,
+    pokedex: &Pokedex,
+    translation_service: &TranslationService,
+) -> Result<HttpResponse, PokeError> {
+    let appearance = pokedex.appearance(pokemon_info).await.unwrap_or_default();
+    let dialect = dialect_for(pokemon_info);
+    let translation = match translation_service
+        .cached(dialect, &pokemon_info.description)
+        .await
+    {
+        Some(translation) => Some(translation),
+        // Browsing cards must not spend the budget the API relies on.
+        None if translation_service.has_quota_left(dialect, 0) => translation_service
+            .translate(dialect, &pokemon_info.description)
+            .await
+            .ok()
+            .map(|chained| chained.translation),
+        None => None,
+    };
+    // A card without its translation is replaced as soon as there is one.
+    let freshness = match translation {
+        Some(_) => freshness,
+        None => Freshness::Revalidate,
+    };
+    let card = PokedexCard {
+        name: &pokemon_info.name,
+        number: pokemon_info.id,
+        // Only ever link images served over https.
+        sprite: appearance
+            .sprite
+            .as_deref()
+            .filter(|sprite| sprite.starts_with("https://")),
+        types: &appearance.types,
+        habitat: pokemon_info.habitat.as_deref().unwrap_or(UNKNOWN_HABITAT),
+        is_legendary: pokemon_info.is_legendary,
+        is_mythical: pokemon_info.is_mythical,
+        original: &pokemon_info.description,
+        translated: translation
+            .as_ref()
+            .map(|translation| translation.translated.as_str()),
+        engine: translation
+            .as_ref()
+            .map(|translation| translation.engine.as_str()),
+    };
+    let body = card.render().map_err(|_| PokeError::internal())?;
+    let mut response = HttpResponse::Ok();
+    response.insert_header((header::VARY, "Accept"));
+    Ok(conditional_html(request, response, body, freshness))
+}
+
+#[cfg(test)]
+mod tests {
+    use askama::Template;
+
+    use crate::routes::card::PokedexCard;
+
+    #[test]
+    fn the_card_escapes_third_party_text() {
+        let types = vec![String::from("psychic")];
+        let card = PokedexCard {
+            name: "mewtwo",
+            number: Some(150),
+            sprite: Some("https://example.com/150.png"),
+            types: &types,
+            habitat: "rare",
+            is_legendary: true,
+            is_mythical: false,
+            original: "It was created by a scientist.",
+            translated: Some("<script>alert('yoda')</script>"),
+            engine: Some("funtranslations"),
+        };
+
+        let html = card.render().unwrap();
+
+        assert!(html.contains("&lt;script&gt;alert(&#x27;yoda&#x27;)&lt;/script&gt;"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains(r#"<img src="https://example.com/150.png""#));
+        assert!(html.contains("#150"));
+        assert!(html.contains(r#"<span class="badge legendary">"#));
+        assert!(!html.contains(r#"<span class="badge mythical">"#));
+    }
+}
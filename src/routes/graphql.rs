@@ -0,0 +1,29 @@
+use actix_web::{get, post, web, HttpResponse};
+use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
+
+use crate::graphql::schema::{with_loaders, PokedexSchema};
+use crate::pokedex::service::Pokedex;
+use crate::translation::service::TranslationService;
+
+#[post("/graphql")]
+pub async fn graphql(
+    request: web::Json<async_graphql::Request>,
+    schema: web::Data<PokedexSchema>,
+    pokedex: web::Data<Pokedex>,
+    translation_service: web::Data<TranslationService>,
+) -> HttpResponse {
+    let request = with_loaders(
+        request.into_inner(),
+        pokedex.into_inner(),
+        translation_service.into_inner(),
+    );
+    HttpResponse::Ok().json(schema.execute(request).await)
+}
+
+/// GraphQL Playground over `/graphql`.
+#[get("/graphql")]
+pub async fn playground() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(playground_source(GraphQLPlaygroundConfig::new("/graphql")))
+}
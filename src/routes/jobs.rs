@@ -0,0 +1,32 @@
+use actix_web::{get, web, HttpResponse};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::error::PokeError;
+use crate::jobs::translation::TranslationJobs;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct JobParams {
+    id: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/jobs/{id}",
+    tag = "jobs",
+    params(JobParams),
+    responses(
+        (status = 200, description = "The job, with its result once done", body = TranslationJob),
+        (status = 404, description = "PE_NOT_FOUND: unknown or expired job", body = PokeError),
+    )
+)]
+#[get("/jobs/{id}")]
+pub async fn get_job(
+    params: web::Path<JobParams>,
+    jobs: web::Data<TranslationJobs>,
+) -> Result<HttpResponse, PokeError> {
+    jobs.get(&params.id)
+        .map(|job| HttpResponse::Ok().json(job))
+        .ok_or_else(|| PokeError::not_found(format!("no job '{}'", params.id)))
+}
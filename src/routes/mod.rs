@@ -0,0 +1,37 @@
+pub mod admin;
+pub mod caching;
+pub mod card;
+pub mod graphql;
+pub mod jobs;
+pub mod openapi;
+pub mod pokemon;
+pub mod pokemon_v2;
+pub mod readiness;
+pub mod translate;
+pub mod versions;
+
+use actix_web::web;
+
+/// Every route of the API, the versioned ones under their scope.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::scope("/v1").configure(versions::v1))
+        .service(web::scope("/v2").configure(versions::v2))
+        .service(graphql::graphql)
+        .service(graphql::playground)
+        .service(jobs::get_job)
+        .service(openapi::openapi_json)
+        .service(openapi::docs)
+        .service(readiness::ready)
+        .service(admin::get_quota)
+        .service(admin::list_cache)
+        .service(admin::warm_cache)
+        .service(admin::get_cache_entry)
+        .service(admin::purge_cache)
+        .service(admin::get_pretranslation)
+        // Unversioned paths alias v1, matched last so they shadow nothing.
+        .service(
+            web::scope("")
+                .wrap(versions::Deprecation)
+                .configure(versions::v1),
+        );
+}
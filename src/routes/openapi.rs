@@ -0,0 +1,345 @@
+use actix_web::{get, HttpResponse};
+use askama::Template;
+use serde_json::Value;
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+
+use crate::cache::entry::{CacheEntry, CacheTier};
+use crate::error::PokeError;
+use crate::jobs::pretranslate::{PretranslationProgress, PretranslationState};
+use crate::jobs::translation::{JobStatus, TranslationJob};
+use crate::jobs::warmup::{WarmupProgress, WarmupState};
+use crate::poke_api::client::{PokemonInfo, Validators};
+use crate::pokedex::service::CachedSpecies;
+use crate::problem::Problem;
+use crate::routes::{admin, jobs, pokemon, pokemon_v2, readiness, translate};
+use crate::translation::chain::ProviderHealth;
+use crate::translation::registry::DialectInfo;
+use crate::translation::service::ProviderQuota;
+use crate::translation::translator::{Capabilities, QuotaStatus, Translation};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "yap_rust",
+        description = "Pokédex with translated descriptions. Errors are documented in their legacy \
+                       `PokeError` shape, served by default; clients naming \
+                       `application/problem+json` in `Accept`, or all of them when configured, \
+                       get `Problem` documents instead. Routes are served under `/v1`, whose shapes are \
+                       frozen, and `/v2`; the unversioned paths are deprecated aliases of `/v1`. \
+                       JSON bodies, errors included, are also served as YAML, CSV, MessagePack \
+                       or plain text, as negotiated by `Accept` or forced by `?format=`. GraphQL \
+                       is served on `/graphql`, with a playground on `GET /graphql`."
+    ),
+    paths(
+        pokemon::list_pokemon,
+        pokemon::get_random_pokemon_info,
+        pokemon::get_daily_pokemon_info,
+        pokemon::get_random_pokemon_info_translated,
+        pokemon::get_daily_pokemon_info_translated,
+        pokemon::get_pokemon_info,
+        pokemon::get_pokemon_info_translated,
+        pokemon_v2::list_pokemon,
+        pokemon_v2::get_random_pokemon_info,
+        pokemon_v2::get_daily_pokemon_info,
+        pokemon_v2::get_random_pokemon_info_translated,
+        pokemon_v2::get_daily_pokemon_info_translated,
+        pokemon_v2::get_pokemon_info,
+        pokemon_v2::get_pokemon_info_translated,
+        pokemon_v2::stream_pokemon_info_translated,
+        translate::list_dialects,
+        translate::list_providers,
+        translate::translate,
+        jobs::get_job,
+        readiness::ready,
+        admin::get_quota,
+        admin::get_pretranslation,
+        admin::list_cache,
+        admin::get_cache_entry,
+        admin::purge_cache,
+        admin::warm_cache,
+    ),
+    components(schemas(
+        PokemonInfo,
+        pokemon::PokemonInfoV1,
+        PokeError,
+        Problem,
+        pokemon::PokemonListV1,
+        pokemon::PokemonListV2,
+        Translation,
+        translate::TranslateRequest,
+        pokemon_v2::StreamRequest,
+        DialectInfo,
+        Capabilities,
+        ProviderHealth,
+        ProviderQuota,
+        QuotaStatus,
+        TranslationJob,
+        JobStatus,
+        readiness::Readiness,
+        WarmupProgress,
+        WarmupState,
+        PretranslationProgress,
+        PretranslationState,
+        CacheEntry,
+        CacheTier,
+        admin::CachedEntry,
+        CachedSpecies,
+        Validators,
+        admin::Purged,
+        admin::WarmRequest,
+        admin::Warmed,
+    )),
+    modifiers(&AdminToken)
+)]
+pub struct ApiDoc;
+
+/// Declares the bearer token the admin routes require.
+struct AdminToken;
+
+impl Modify for AdminToken {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "admin_token",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+        }
+    }
+}
+
+#[get("/openapi.json")]
+pub async fn openapi_json() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+/// Reference of every documented route, rendered from the same document as
+/// `/openapi.json`. Everything is served from here: the page loads no script
+/// or stylesheet from elsewhere.
+#[get("/docs")]
+pub async fn docs() -> Result<HttpResponse, PokeError> {
+    let body = DocsPage::new(&ApiDoc::openapi())
+        .render()
+        .map_err(|_| PokeError::internal())?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(body))
+}
+
+#[derive(Template)]
+#[template(path = "docs.html")]
+struct DocsPage {
+    title: String,
+    description: String,
+    operations: Vec<DocsOperation>,
+}
+
+struct DocsOperation {
+    method: String,
+    path: String,
+    summary: Option<String>,
+    parameters: Vec<DocsParameter>,
+    responses: Vec<DocsResponse>,
+}
+
+struct DocsParameter {
+    name: String,
+    location: String,
+    required: bool,
+    description: String,
+}
+
+struct DocsResponse {
+    status: String,
+    description: String,
+}
+
+impl DocsPage {
+    fn new(openapi: &utoipa::openapi::OpenApi) -> Self {
+        let document = serde_json::to_value(openapi).unwrap_or_default();
+        let text = |value: &Value| String::from(value.as_str().unwrap_or_default());
+        let mut operations = Vec::new();
+        for (path, item) in document["paths"].as_object().into_iter().flatten() {
+            for (method, operation) in item.as_object().into_iter().flatten() {
+                operations.push(DocsOperation {
+                    method: method.clone(),
+                    path: path.clone(),
+                    summary: operation["summary"]
+                        .as_str()
+                        .or_else(|| operation["description"].as_str())
+                        .map(String::from),
+                    parameters: operation["parameters"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .map(|parameter| DocsParameter {
+                            name: text(&parameter["name"]),
+                            location: text(&parameter["in"]),
+                            required: parameter["required"].as_bool().unwrap_or(false),
+                            description: text(&parameter["description"]),
+                        })
+                        .collect(),
+                    responses: operation["responses"]
+                        .as_object()
+                        .into_iter()
+                        .flatten()
+                        .map(|(status, response)| DocsResponse {
+                            status: status.clone(),
+                            description: text(&response["description"]),
+                        })
+                        .collect(),
+                });
+            }
+        }
+        DocsPage {
+            title: openapi.info.title.clone(),
+            description: openapi.info.description.clone().unwrap_or_default(),
+            operations,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, str::FromStr};
+
+    use actix_web::{
+        http::{Method, StatusCode},
+        test, App,
+    };
+    use askama::Template;
+    use serde_json::Value;
+    use utoipa::OpenApi;
+
+    use crate::routes;
+    use crate::routes::openapi::{ApiDoc, DocsPage};
+
+    const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+    /// `(method, path)` of every documented operation.
+    fn documented_routes(openapi: &Value) -> BTreeSet<(String, String)> {
+        let mut routes = BTreeSet::new();
+        for (path, item) in openapi["paths"].as_object().unwrap() {
+            for method in item.as_object().unwrap().keys() {
+                routes.insert((method.clone(), path.clone()));
+            }
+        }
+        routes
+    }
+
+    fn matches_pattern(path: &str, pattern: &str) -> bool {
+        let (path, pattern) = (path.split('/'), pattern.split('/'));
+        path.clone().count() == pattern.clone().count()
+            && path
+                .zip(pattern)
+                .all(|(segment, expected)| segment == expected || expected.starts_with('{'))
+    }
+
+    /// Whether a request for `path`, its parameters filled in, reaches a
+    /// handler in the routes the server is built with. Without app data,
+    /// handlers fail rather than answer, but never with a 404 or 405.
+    async fn is_reached(method: &str, path: &str) -> bool {
+        let app = test::init_service(App::new().configure(routes::configure)).await;
+        let request = test::TestRequest::default()
+            .method(Method::from_str(&method.to_uppercase()).unwrap())
+            .uri(&path.replace(['{', '}'], ""))
+            .to_request();
+        let status = test::call_service(&app, request).await.status();
+        status != StatusCode::NOT_FOUND && status != StatusCode::METHOD_NOT_ALLOWED
+    }
+
+    #[actix_rt::test]
+    async fn the_document_covers_exactly_the_served_routes() {
+        let openapi = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let documented = documented_routes(&openapi);
+
+        for (method, path) in &documented {
+            assert!(
+                is_reached(method, path).await,
+                "{} {} is not served",
+                method,
+                path
+            );
+            // The unversioned aliases of v1 are served too.
+            if let Some(alias) = path.strip_prefix("/v1") {
+                assert!(
+                    is_reached(method, alias).await,
+                    "{} {} is not served",
+                    method,
+                    alias
+                );
+            }
+            for other in METHODS {
+                // Another route may take the path as a parameter, like
+                // `POST /v1/translate/{dialect}` does `/v1/translate/dialects`.
+                let documented_for_other = documented.iter().any(|(method, pattern)| {
+                    method == other && matches_pattern(&path.replace(['{', '}'], ""), pattern)
+                });
+                if !documented_for_other {
+                    assert!(
+                        !is_reached(other, path).await,
+                        "{} {} is served but not documented",
+                        other,
+                        path
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_document_declares_path_parameters_and_referenced_schemas() {
+        let openapi = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let schemas = openapi["components"]["schemas"].as_object().unwrap();
+
+        for (path, item) in openapi["paths"].as_object().unwrap() {
+            for (method, operation) in item.as_object().unwrap() {
+                let parameters = operation["parameters"].as_array().cloned();
+                for segment in path.split('/').filter(|segment| segment.starts_with('{')) {
+                    let name = segment.trim_matches(|c| c == '{' || c == '}');
+                    let declared = parameters
+                        .iter()
+                        .flatten()
+                        .any(|parameter| parameter["in"] == "path" && parameter["name"] == name);
+                    assert!(declared, "{} {} does not document '{}'", method, path, name);
+                }
+                for status in operation["responses"].as_object().unwrap().keys() {
+                    assert!(
+                        StatusCode::from_str(status).is_ok(),
+                        "{} {} documents status {}",
+                        method,
+                        path,
+                        status
+                    );
+                }
+            }
+        }
+        let document = openapi.to_string();
+        for reference in document.split("\"#/components/schemas/").skip(1) {
+            let name = &reference[..reference.find('"').unwrap()];
+            assert!(
+                schemas.contains_key(name),
+                "schema {} is not declared",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn the_docs_page_is_self_contained() {
+        let page = DocsPage::new(&ApiDoc::openapi());
+        let operations = page.operations.len();
+        let html = page.render().unwrap();
+
+        assert!(operations > 0);
+        assert_eq!(
+            html.matches(r#"<section class="operation">"#).count(),
+            operations
+        );
+        assert!(html.contains("PE_NOT_FOUND"));
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("https://"));
+    }
+}
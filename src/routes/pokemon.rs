@@ -0,0 +1,501 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::{get, http::header, web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::configuration::Settings;
+use crate::error::PokeError;
+use crate::format::wants_html;
+use crate::jobs::translation::{TranslationJob, TranslationJobs};
+use crate::poke_api::client::PokemonInfo;
+use crate::pokedex::service::{dialect_for, Pokedex, SpeciesFilter};
+use crate::routes::caching::{conditional_json, mark_staleness, Freshness};
+use crate::routes::card;
+use crate::translation::chain::ChainedTranslation;
+use crate::translation::service::TranslationService;
+
+const DEFAULT_PAGE_LIMIT: usize = 20;
+const MAX_PAGE_LIMIT: usize = 100;
+pub const TRANSLATION_ENGINE_HEADER: &str = "X-Translation-Engine";
+pub const TRANSLATION_ATTEMPTS_HEADER: &str = "X-Translation-Attempts";
+/// Reported by v1 for species without a habitat, which it cannot leave out.
+const UNKNOWN_HABITAT: &str = "unknown";
+
+/// `PokemonInfo` as served by v1, frozen: fields added since are left out.
+#[derive(Serialize, ToSchema)]
+pub struct PokemonInfoV1 {
+    pub name: String,
+    pub description: String,
+    pub habitat: String,
+    pub is_legendary: bool,
+}
+
+impl From<PokemonInfo> for PokemonInfoV1 {
+    fn from(pokemon_info: PokemonInfo) -> Self {
+        PokemonInfoV1 {
+            name: pokemon_info.name,
+            description: pokemon_info.description,
+            habitat: pokemon_info
+                .habitat
+                .unwrap_or_else(|| String::from(UNKNOWN_HABITAT)),
+            is_legendary: pokemon_info.is_legendary,
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct PathParams {
+    /// Species name, as listed by PokeAPI.
+    name: String,
+}
+
+#[derive(Clone, Deserialize, Serialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset: Option<usize>,
+    /// Page size, 20 by default and at most 100.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit: Option<usize>,
+    /// Generation name, e.g. `generation-i`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    generation: Option<String>,
+    /// Habitat name, e.g. `cave`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    habitat: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    legendary: Option<bool>,
+    /// Response format, kept on the `next` and `previous` links.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RandomParams {
+    generation: Option<String>,
+    habitat: Option<String>,
+    legendary: Option<bool>,
+}
+
+impl From<&RandomParams> for SpeciesFilter {
+    fn from(params: &RandomParams) -> Self {
+        SpeciesFilter {
+            generation: params.generation.clone(),
+            habitat: params.habitat.clone(),
+            legendary: params.legendary,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+#[aliases(PokemonListV1 = PokemonList<PokemonInfoV1>, PokemonListV2 = PokemonList<PokemonInfo>)]
+pub struct PokemonList<R> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    count: Option<usize>,
+    next: Option<String>,
+    previous: Option<String>,
+    results: Vec<R>,
+}
+
+impl ListParams {
+    /// Link to another page, on the path the current one was requested on.
+    fn link(&self, path: &str, offset: usize, limit: usize) -> String {
+        let params = ListParams {
+            offset: Some(offset),
+            limit: Some(limit),
+            ..self.clone()
+        };
+        format!("{}?{}", path, serde_urlencoded::to_string(params).unwrap())
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/pokemon",
+    context_path = "/v1",
+    tag = "pokemon",
+    params(ListParams),
+    responses(
+        (status = 200, description = "A page of species", body = PokemonListV1),
+        (status = 400, description = "PE_BAD_REQUEST: invalid limit, generation or habitat, or a legendary filter scanning too many species", body = PokeError),
+        (status = 500, description = "PE_INTERNAL", body = PokeError),
+    )
+)]
+#[get("/pokemon")]
+pub async fn list_pokemon(
+    request: HttpRequest,
+    params: web::Query<ListParams>,
+    pokedex: web::Data<Pokedex>,
+) -> Result<HttpResponse, PokeError> {
+    list::<PokemonInfoV1>(&request, &params, &pokedex).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/pokemon/random",
+    context_path = "/v1",
+    tag = "pokemon",
+    params(RandomParams),
+    responses(
+        (status = 200, description = "A random species matching the filters", body = PokemonInfoV1),
+        (status = 400, description = "PE_BAD_REQUEST: unknown generation or habitat", body = PokeError),
+        (status = 404, description = "PE_NOT_FOUND: no species matches", body = PokeError),
+        (status = 500, description = "PE_INTERNAL", body = PokeError),
+    )
+)]
+#[get("/pokemon/random")]
+pub async fn get_random_pokemon_info(
+    params: web::Query<RandomParams>,
+    pokedex: web::Data<Pokedex>,
+) -> Result<HttpResponse, PokeError> {
+    let pokemon_info = pokedex.random(&SpeciesFilter::from(&*params)).await?;
+    Ok(HttpResponse::Ok().json(PokemonInfoV1::from(pokemon_info)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/pokemon/daily",
+    context_path = "/v1",
+    tag = "pokemon",
+    responses(
+        (status = 200, description = "The species of the day", body = PokemonInfoV1),
+        (status = 500, description = "PE_INTERNAL", body = PokeError),
+    )
+)]
+#[get("/pokemon/daily")]
+pub async fn get_daily_pokemon_info(
+    pokedex: web::Data<Pokedex>,
+    settings: web::Data<Settings>,
+) -> Result<HttpResponse, PokeError> {
+    let pokemon_info = pokedex.daily(settings.daily_seed, today()).await?;
+    Ok(HttpResponse::Ok().json(PokemonInfoV1::from(pokemon_info)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/pokemon/translated/random",
+    context_path = "/v1",
+    tag = "pokemon",
+    params(RandomParams),
+    responses(
+        (status = 200, description = "A random species, with its description translated", body = PokemonInfoV1, headers(
+            ("X-Translation-Engine" = String, description = "Provider that translated the description"),
+            ("X-Translation-Attempts" = u32, description = "Providers called, zero for cached translations"),
+        )),
+        (status = 400, description = "PE_BAD_REQUEST", body = PokeError),
+        (status = 404, description = "PE_NOT_FOUND", body = PokeError),
+        (status = 429, description = "PE_RATE_LIMITED or PE_QUOTA_EXHAUSTED", body = PokeError),
+        (status = 503, description = "PE_UNAVAILABLE", body = PokeError),
+        (status = 504, description = "PE_TIMEOUT", body = PokeError),
+        (status = 500, description = "PE_INTERNAL", body = PokeError),
+    )
+)]
+#[get("/pokemon/translated/random")]
+pub async fn get_random_pokemon_info_translated(
+    params: web::Query<RandomParams>,
+    pokedex: web::Data<Pokedex>,
+    translation_service: web::Data<TranslationService>,
+) -> Result<HttpResponse, PokeError> {
+    let pokemon_info = pokedex.random(&SpeciesFilter::from(&*params)).await?;
+    translated::<PokemonInfoV1>(pokemon_info, &translation_service).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/pokemon/translated/daily",
+    context_path = "/v1",
+    tag = "pokemon",
+    responses(
+        (status = 200, description = "The species of the day, with its description translated", body = PokemonInfoV1, headers(
+            ("X-Translation-Engine" = String, description = "Provider that translated the description"),
+            ("X-Translation-Attempts" = u32, description = "Providers called, zero for cached translations"),
+        )),
+        (status = 429, description = "PE_RATE_LIMITED or PE_QUOTA_EXHAUSTED", body = PokeError),
+        (status = 503, description = "PE_UNAVAILABLE", body = PokeError),
+        (status = 504, description = "PE_TIMEOUT", body = PokeError),
+        (status = 500, description = "PE_INTERNAL", body = PokeError),
+    )
+)]
+#[get("/pokemon/translated/daily")]
+pub async fn get_daily_pokemon_info_translated(
+    pokedex: web::Data<Pokedex>,
+    translation_service: web::Data<TranslationService>,
+    settings: web::Data<Settings>,
+) -> Result<HttpResponse, PokeError> {
+    let pokemon_info = pokedex.daily(settings.daily_seed, today()).await?;
+    translated::<PokemonInfoV1>(pokemon_info, &translation_service).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/pokemon/{name}",
+    context_path = "/v1",
+    tag = "pokemon",
+    params(PathParams),
+    responses(
+        (status = 200, description = "The species, or its Pokédex card as an HTML page when `Accept` prefers `text/html`", body = PokemonInfoV1, headers(
+            ("ETag" = String),
+            ("Cache-Control" = String),
+        )),
+        (status = 304, description = "Not modified since the `If-None-Match` tag"),
+        (status = 404, description = "PE_NOT_FOUND", body = PokeError),
+        (status = 500, description = "PE_INTERNAL", body = PokeError),
+    )
+)]
+#[get("/pokemon/{name}")]
+pub async fn get_pokemon_info(
+    request: HttpRequest,
+    info: web::Path<PathParams>,
+    pokedex: web::Data<Pokedex>,
+    translation_service: web::Data<TranslationService>,
+    settings: web::Data<Settings>,
+) -> Result<HttpResponse, PokeError> {
+    by_name::<PokemonInfoV1>(&request, &info, &pokedex, &translation_service, &settings).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/pokemon/translated/{name}",
+    context_path = "/v1",
+    tag = "pokemon",
+    params(PathParams),
+    responses(
+        (status = 200, description = "The species, with its description translated", body = PokemonInfoV1, headers(
+            ("X-Translation-Engine" = String, description = "Provider that translated the description"),
+            ("X-Translation-Attempts" = u32, description = "Providers called, zero for cached translations"),
+            ("ETag" = String),
+            ("Cache-Control" = String),
+        )),
+        (status = 202, description = "Out of quota: the translation was queued as a job", body = TranslationJob, headers(
+            ("Location" = String, description = "Where to poll the job"),
+        )),
+        (status = 304, description = "Not modified since the `If-None-Match` tag"),
+        (status = 404, description = "PE_NOT_FOUND", body = PokeError),
+        (status = 429, description = "PE_RATE_LIMITED, PE_QUOTA_EXHAUSTED or PE_QUEUE_FULL when jobs are enabled", body = PokeError),
+        (status = 503, description = "PE_UNAVAILABLE", body = PokeError),
+        (status = 504, description = "PE_TIMEOUT", body = PokeError),
+        (status = 500, description = "PE_INTERNAL", body = PokeError),
+    )
+)]
+#[get("/pokemon/translated/{name}")]
+pub async fn get_pokemon_info_translated(
+    request: HttpRequest,
+    info: web::Path<PathParams>,
+    pokedex: web::Data<Pokedex>,
+    translation_service: web::Data<TranslationService>,
+    jobs: web::Data<TranslationJobs>,
+    settings: web::Data<Settings>,
+) -> Result<HttpResponse, PokeError> {
+    translated_by_name::<PokemonInfoV1>(
+        &request,
+        &info,
+        &pokedex,
+        &translation_service,
+        &jobs,
+        &settings,
+    )
+    .await
+}
+
+/// A page of species, each served as `R`.
+pub async fn list<R: From<PokemonInfo> + Serialize>(
+    request: &HttpRequest,
+    params: &ListParams,
+    pokedex: &Pokedex,
+) -> Result<HttpResponse, PokeError> {
+    let offset = params.offset.unwrap_or(0);
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    if limit == 0 || limit > MAX_PAGE_LIMIT {
+        return Err(PokeError::bad_request(format!(
+            "limit must be between 1 and {}",
+            MAX_PAGE_LIMIT
+        )));
+    }
+    let filter = SpeciesFilter {
+        generation: params.generation.clone(),
+        habitat: params.habitat.clone(),
+        legendary: params.legendary,
+    };
+    let page = pokedex.list(&filter, offset, limit).await?;
+    let list = PokemonList {
+        count: page.count,
+        next: if page.has_more {
+            Some(params.link(request.path(), offset + limit, limit))
+        } else {
+            None
+        },
+        previous: if offset > 0 {
+            Some(params.link(request.path(), offset.saturating_sub(limit), limit))
+        } else {
+            None
+        },
+        results: page.results.into_iter().map(R::from).collect::<Vec<R>>(),
+    };
+    Ok(HttpResponse::Ok().json(list))
+}
+
+/// The species, served as `R` with its cache validators, or as its Pokédex
+/// card to clients asking for HTML.
+pub async fn by_name<R: From<PokemonInfo> + Serialize>(
+    request: &HttpRequest,
+    info: &PathParams,
+    pokedex: &Pokedex,
+    translation_service: &TranslationService,
+    settings: &Settings,
+) -> Result<HttpResponse, PokeError> {
+    let (pokemon_info, staleness) = pokedex.lookup(&info.name).await?;
+    let freshness = Freshness::unless_stale(settings.pokemon_info_max_age, staleness);
+    if wants_html(request.head()) {
+        return card::render(
+            request,
+            &pokemon_info,
+            freshness,
+            pokedex,
+            translation_service,
+        )
+        .await;
+    }
+    // The body only depends on the species, so PokeAPI's date applies.
+    let last_modified = pokedex
+        .cached_entry(&info.name)
+        .await
+        .and_then(|cached| cached.validators.last_modified?.parse().ok());
+    let mut response = HttpResponse::Ok();
+    mark_staleness(&mut response, staleness);
+    Ok(conditional_json(
+        request,
+        response,
+        &R::from(pokemon_info),
+        last_modified,
+        freshness,
+    ))
+}
+
+/// The species with its description translated, served as `R`, or the job
+/// translating it when no provider can right now.
+pub async fn translated_by_name<R: From<PokemonInfo> + Serialize>(
+    request: &HttpRequest,
+    info: &PathParams,
+    pokedex: &Pokedex,
+    translation_service: &TranslationService,
+    jobs: &TranslationJobs,
+    settings: &Settings,
+) -> Result<HttpResponse, PokeError> {
+    let (pokemon_info, staleness) = pokedex.lookup(&info.name).await?;
+    let (pokemon_info, chained) =
+        match translate_or_queue(pokemon_info, translation_service, jobs).await? {
+            Translated::Done(pokemon_info, chained) => (pokemon_info, chained),
+            Translated::Queued(job) => {
+                return Ok(HttpResponse::Accepted()
+                    .insert_header((header::LOCATION, format!("/jobs/{}", job.id)))
+                    .json(job))
+            }
+        };
+    let staleness = staleness.max(chained.staleness);
+    // Only answers from the preferred provider are cached by us, so only
+    // those may be kept downstream for as long.
+    let freshness = if chained.preferred {
+        Freshness::unless_stale(settings.translation_cache_ttl, staleness)
+    } else {
+        Freshness::Revalidate
+    };
+    let mut response = HttpResponse::Ok();
+    response
+        .insert_header((TRANSLATION_ENGINE_HEADER, chained.translation.engine))
+        .insert_header((TRANSLATION_ATTEMPTS_HEADER, chained.attempts.to_string()));
+    mark_staleness(&mut response, staleness);
+    Ok(conditional_json(
+        request,
+        response,
+        &R::from(pokemon_info),
+        None,
+        freshness,
+    ))
+}
+
+/// A species with its description translated, or the job translating it.
+pub enum Translated {
+    Done(PokemonInfo, ChainedTranslation),
+    Queued(TranslationJob),
+}
+
+/// Translates the description of `pokemon_info`, or queues its translation
+/// when no provider can right now and jobs are enabled.
+pub async fn translate_or_queue(
+    mut pokemon_info: PokemonInfo,
+    translation_service: &TranslationService,
+    jobs: &TranslationJobs,
+) -> Result<Translated, PokeError> {
+    let chained = match translation_service
+        .translate(dialect_for(&pokemon_info), &pokemon_info.description)
+        .await
+    {
+        Ok(chained) => chained,
+        Err(error) if jobs.accepts(&error) => {
+            return Ok(Translated::Queued(jobs.submit(pokemon_info)?))
+        }
+        Err(error) => return Err(error.into()),
+    };
+    pokemon_info.description = chained.translation.translated.clone();
+    Ok(Translated::Done(pokemon_info, chained))
+}
+
+/// `pokemon_info` with its description translated, served as `R`.
+pub async fn translated<R: From<PokemonInfo> + Serialize>(
+    pokemon_info: PokemonInfo,
+    translation_service: &TranslationService,
+) -> Result<HttpResponse, PokeError> {
+    let (pokemon_info, chained) = translate_pokemon_info(pokemon_info, translation_service).await?;
+    let mut response = HttpResponse::Ok();
+    response
+        .insert_header((TRANSLATION_ENGINE_HEADER, chained.translation.engine))
+        .insert_header((TRANSLATION_ATTEMPTS_HEADER, chained.attempts.to_string()));
+    mark_staleness(&mut response, chained.staleness);
+    Ok(response.json(R::from(pokemon_info)))
+}
+
+/// Replaces the description with its translation, also returning how the
+/// translation was obtained.
+pub async fn translate_pokemon_info(
+    mut pokemon_info: PokemonInfo,
+    translation_service: &TranslationService,
+) -> Result<(PokemonInfo, ChainedTranslation), PokeError> {
+    let chained = translation_service
+        .translate(dialect_for(&pokemon_info), &pokemon_info.description)
+        .await?;
+    pokemon_info.description = chained.translation.translated.clone();
+    Ok((pokemon_info, chained))
+}
+
+/// Days elapsed since the unix epoch, in UTC.
+pub fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        / (24 * 60 * 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::routes::pokemon::ListParams;
+
+    #[test]
+    fn links_keep_the_requested_format() {
+        // Arrange
+        let params: ListParams =
+            serde_urlencoded::from_str("offset=20&limit=10&habitat=cave&format=yaml").unwrap();
+
+        // Act
+        let link = params.link("/v1/pokemon", 30, 10);
+
+        // Assert
+        assert_eq!(
+            link,
+            "/v1/pokemon?offset=30&limit=10&habitat=cave&format=yaml"
+        );
+    }
+}
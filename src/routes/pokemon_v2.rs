@@ -0,0 +1,425 @@
+use actix_web::{
+    get,
+    http::header::{CACHE_CONTROL, CONTENT_TYPE},
+    post,
+    web::{self, Bytes},
+    HttpRequest, HttpResponse,
+};
+use futures::{
+    channel::mpsc::{self, UnboundedSender},
+    stream, StreamExt,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::configuration::Settings;
+use crate::error::PokeError;
+use crate::jobs::translation::TranslationJobs;
+use crate::poke_api::client::PokemonInfo;
+use crate::pokedex::service::{Pokedex, SpeciesFilter};
+use crate::routes::pokemon::{
+    by_name, list, today, translate_or_queue, translated, translated_by_name, ListParams,
+    PathParams, RandomParams, Translated,
+};
+use crate::translation::service::TranslationService;
+
+/// Most species a single stream may ask for.
+const MAX_STREAM_NAMES: usize = 100;
+/// Species fetched and translated at the same time for a stream.
+const STREAM_CONCURRENCY: usize = 8;
+
+#[derive(Deserialize, ToSchema)]
+pub struct StreamRequest {
+    /// Species names or national dex numbers.
+    names: Vec<String>,
+}
+
+/// Data of `error` events, naming the species it concerns.
+#[derive(Serialize)]
+struct StreamError<'a> {
+    name: &'a str,
+    #[serde(flatten)]
+    error: PokeError,
+}
+
+#[utoipa::path(
+    get,
+    path = "/pokemon",
+    context_path = "/v2",
+    tag = "pokemon",
+    params(ListParams),
+    responses(
+        (status = 200, description = "A page of species", body = PokemonListV2),
+        (status = 400, description = "PE_BAD_REQUEST: invalid limit, generation or habitat, or a legendary filter scanning too many species", body = PokeError),
+        (status = 500, description = "PE_INTERNAL", body = PokeError),
+    )
+)]
+#[get("/pokemon")]
+pub async fn list_pokemon(
+    request: HttpRequest,
+    params: web::Query<ListParams>,
+    pokedex: web::Data<Pokedex>,
+) -> Result<HttpResponse, PokeError> {
+    list::<PokemonInfo>(&request, &params, &pokedex).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/pokemon/random",
+    context_path = "/v2",
+    tag = "pokemon",
+    params(RandomParams),
+    responses(
+        (status = 200, description = "A random species matching the filters", body = PokemonInfo),
+        (status = 400, description = "PE_BAD_REQUEST: unknown generation or habitat", body = PokeError),
+        (status = 404, description = "PE_NOT_FOUND: no species matches", body = PokeError),
+        (status = 500, description = "PE_INTERNAL", body = PokeError),
+    )
+)]
+#[get("/pokemon/random")]
+pub async fn get_random_pokemon_info(
+    params: web::Query<RandomParams>,
+    pokedex: web::Data<Pokedex>,
+) -> Result<HttpResponse, PokeError> {
+    let pokemon_info = pokedex.random(&SpeciesFilter::from(&*params)).await?;
+    Ok(HttpResponse::Ok().json(pokemon_info))
+}
+
+#[utoipa::path(
+    get,
+    path = "/pokemon/daily",
+    context_path = "/v2",
+    tag = "pokemon",
+    responses(
+        (status = 200, description = "The species of the day", body = PokemonInfo),
+        (status = 500, description = "PE_INTERNAL", body = PokeError),
+    )
+)]
+#[get("/pokemon/daily")]
+pub async fn get_daily_pokemon_info(
+    pokedex: web::Data<Pokedex>,
+    settings: web::Data<Settings>,
+) -> Result<HttpResponse, PokeError> {
+    let pokemon_info = pokedex.daily(settings.daily_seed, today()).await?;
+    Ok(HttpResponse::Ok().json(pokemon_info))
+}
+
+#[utoipa::path(
+    get,
+    path = "/pokemon/translated/random",
+    context_path = "/v2",
+    tag = "pokemon",
+    params(RandomParams),
+    responses(
+        (status = 200, description = "A random species, with its description translated", body = PokemonInfo, headers(
+            ("X-Translation-Engine" = String, description = "Provider that translated the description"),
+            ("X-Translation-Attempts" = u32, description = "Providers called, zero for cached translations"),
+        )),
+        (status = 400, description = "PE_BAD_REQUEST", body = PokeError),
+        (status = 404, description = "PE_NOT_FOUND", body = PokeError),
+        (status = 429, description = "PE_RATE_LIMITED or PE_QUOTA_EXHAUSTED", body = PokeError),
+        (status = 503, description = "PE_UNAVAILABLE", body = PokeError),
+        (status = 504, description = "PE_TIMEOUT", body = PokeError),
+        (status = 500, description = "PE_INTERNAL", body = PokeError),
+    )
+)]
+#[get("/pokemon/translated/random")]
+pub async fn get_random_pokemon_info_translated(
+    params: web::Query<RandomParams>,
+    pokedex: web::Data<Pokedex>,
+    translation_service: web::Data<TranslationService>,
+) -> Result<HttpResponse, PokeError> {
+    let pokemon_info = pokedex.random(&SpeciesFilter::from(&*params)).await?;
+    translated::<PokemonInfo>(pokemon_info, &translation_service).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/pokemon/translated/daily",
+    context_path = "/v2",
+    tag = "pokemon",
+    responses(
+        (status = 200, description = "The species of the day, with its description translated", body = PokemonInfo, headers(
+            ("X-Translation-Engine" = String, description = "Provider that translated the description"),
+            ("X-Translation-Attempts" = u32, description = "Providers called, zero for cached translations"),
+        )),
+        (status = 429, description = "PE_RATE_LIMITED or PE_QUOTA_EXHAUSTED", body = PokeError),
+        (status = 503, description = "PE_UNAVAILABLE", body = PokeError),
+        (status = 504, description = "PE_TIMEOUT", body = PokeError),
+        (status = 500, description = "PE_INTERNAL", body = PokeError),
+    )
+)]
+#[get("/pokemon/translated/daily")]
+pub async fn get_daily_pokemon_info_translated(
+    pokedex: web::Data<Pokedex>,
+    translation_service: web::Data<TranslationService>,
+    settings: web::Data<Settings>,
+) -> Result<HttpResponse, PokeError> {
+    let pokemon_info = pokedex.daily(settings.daily_seed, today()).await?;
+    translated::<PokemonInfo>(pokemon_info, &translation_service).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/pokemon/{name}",
+    context_path = "/v2",
+    tag = "pokemon",
+    params(PathParams),
+    responses(
+        (status = 200, description = "The species, or its Pokédex card as an HTML page when `Accept` prefers `text/html`", body = PokemonInfo, headers(
+            ("ETag" = String),
+            ("Cache-Control" = String),
+        )),
+        (status = 304, description = "Not modified since the `If-None-Match` tag"),
+        (status = 404, description = "PE_NOT_FOUND", body = PokeError),
+        (status = 500, description = "PE_INTERNAL", body = PokeError),
+    )
+)]
+#[get("/pokemon/{name}")]
+pub async fn get_pokemon_info(
+    request: HttpRequest,
+    info: web::Path<PathParams>,
+    pokedex: web::Data<Pokedex>,
+    translation_service: web::Data<TranslationService>,
+    settings: web::Data<Settings>,
+) -> Result<HttpResponse, PokeError> {
+    by_name::<PokemonInfo>(&request, &info, &pokedex, &translation_service, &settings).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/pokemon/translated/{name}",
+    context_path = "/v2",
+    tag = "pokemon",
+    params(PathParams),
+    responses(
+        (status = 200, description = "The species, with its description translated", body = PokemonInfo, headers(
+            ("X-Translation-Engine" = String, description = "Provider that translated the description"),
+            ("X-Translation-Attempts" = u32, description = "Providers called, zero for cached translations"),
+            ("ETag" = String),
+            ("Cache-Control" = String),
+        )),
+        (status = 202, description = "Out of quota: the translation was queued as a job", body = TranslationJob, headers(
+            ("Location" = String, description = "Where to poll the job"),
+        )),
+        (status = 304, description = "Not modified since the `If-None-Match` tag"),
+        (status = 404, description = "PE_NOT_FOUND", body = PokeError),
+        (status = 429, description = "PE_RATE_LIMITED, PE_QUOTA_EXHAUSTED or PE_QUEUE_FULL when jobs are enabled", body = PokeError),
+        (status = 503, description = "PE_UNAVAILABLE", body = PokeError),
+        (status = 504, description = "PE_TIMEOUT", body = PokeError),
+        (status = 500, description = "PE_INTERNAL", body = PokeError),
+    )
+)]
+#[get("/pokemon/translated/{name}")]
+pub async fn get_pokemon_info_translated(
+    request: HttpRequest,
+    info: web::Path<PathParams>,
+    pokedex: web::Data<Pokedex>,
+    translation_service: web::Data<TranslationService>,
+    jobs: web::Data<TranslationJobs>,
+    settings: web::Data<Settings>,
+) -> Result<HttpResponse, PokeError> {
+    translated_by_name::<PokemonInfo>(
+        &request,
+        &info,
+        &pokedex,
+        &translation_service,
+        &jobs,
+        &settings,
+    )
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/pokemon/translated/stream",
+    context_path = "/v2",
+    tag = "pokemon",
+    request_body = StreamRequest,
+    responses(
+        (status = 200, description = "Server-sent events, per species: `info` with it as soon as it is fetched, then `translation` with its description translated, or `job` with the job its translation was queued as; `error` with `name`, `code` and `message` when either step fails. A last `done` event follows once every species is settled.", content_type = "text/event-stream", body = String),
+        (status = 400, description = "PE_BAD_REQUEST: no names, or more than 100", body = PokeError),
+    )
+)]
+#[post("/pokemon/translated/stream")]
+pub async fn stream_pokemon_info_translated(
+    request: web::Json<StreamRequest>,
+    pokedex: web::Data<Pokedex>,
+    translation_service: web::Data<TranslationService>,
+    jobs: web::Data<TranslationJobs>,
+) -> Result<HttpResponse, PokeError> {
+    let names = request.into_inner().names;
+    if names.is_empty() || names.len() > MAX_STREAM_NAMES {
+        return Err(PokeError::bad_request(format!(
+            "names must list between 1 and {} species",
+            MAX_STREAM_NAMES
+        )));
+    }
+    let (sender, receiver) = mpsc::unbounded();
+    actix_web::rt::spawn(async move {
+        stream::iter(names)
+            .for_each_concurrent(STREAM_CONCURRENCY, |name| {
+                let (sender, pokedex, translation_service, jobs) =
+                    (&sender, &pokedex, &translation_service, &jobs);
+                async move {
+                    stream_one(&name, pokedex, translation_service, jobs, sender).await;
+                }
+            })
+            .await;
+        send(&sender, "done", &serde_json::json!({}));
+    });
+    Ok(HttpResponse::Ok()
+        .insert_header((CONTENT_TYPE, "text/event-stream"))
+        .insert_header((CACHE_CONTROL, "no-cache"))
+        .streaming(receiver.map(Ok::<Bytes, actix_web::Error>)))
+}
+
+/// Sends the events of a single species: its info, then its translation.
+async fn stream_one(
+    name: &str,
+    pokedex: &Pokedex,
+    translation_service: &TranslationService,
+    jobs: &TranslationJobs,
+    sender: &UnboundedSender<Bytes>,
+) {
+    let pokemon_info = match pokedex.lookup(name).await {
+        Ok((pokemon_info, _)) => pokemon_info,
+        Err(error) => return send_error(sender, name, error.into()),
+    };
+    send(sender, "info", &pokemon_info);
+    // The client went away: spare the translation quota.
+    if sender.is_closed() {
+        return;
+    }
+    match translate_or_queue(pokemon_info, translation_service, jobs).await {
+        Ok(Translated::Done(pokemon_info, _)) => send(sender, "translation", &pokemon_info),
+        Ok(Translated::Queued(job)) => send(sender, "job", &job),
+        Err(error) => send_error(sender, name, error),
+    }
+}
+
+fn send_error(sender: &UnboundedSender<Bytes>, name: &str, error: PokeError) {
+    send(sender, "error", &StreamError { name, error });
+}
+
+/// Sends a server-sent event, unless the client is gone.
+fn send<T: Serialize>(sender: &UnboundedSender<Bytes>, event: &str, data: &T) {
+    let _ = sender.unbounded_send(Bytes::from(format!(
+        "event: {}\ndata: {}\n\n",
+        event,
+        serde_json::to_string(data).unwrap()
+    )));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc, time::Duration};
+
+    use actix_web::{test, web::Data, App};
+    use serde_json::{json, Value};
+    use wiremock::{matchers::path, Mock, MockServer, ResponseTemplate};
+
+    use crate::{
+        cache::memory::TtlCache,
+        funtranslations_api::client::FunTranslationsApiClient,
+        jobs::translation::TranslationJobs,
+        poke_api::client::PokeApiClient,
+        pokedex::service::Pokedex,
+        routes::pokemon_v2::stream_pokemon_info_translated,
+        translation::{
+            chain::{HealthPolicy, TranslatorChain},
+            registry::TranslatorRegistry,
+            service::TranslationService,
+        },
+    };
+
+    #[actix_rt::test]
+    async fn stream_sends_each_species_before_its_translation() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/pokemon-species/mewtwo"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"name": "mewtwo", "is_legendary": true, "flavor_text_entries": [{"flavor_text": "It was created by a scientist.", "language": {"name": "en"}}]})))
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/pokemon-species/missingno"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/translate/yoda.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                r#"{"success":{"total":1},"contents":{"translated":"Created by a scientist, it was.","text":"It was created by a scientist.","translation":"yoda"}}"#,
+            ))
+            .mount(&mock_server)
+            .await;
+        let app = test::init_service(
+            App::new()
+                .service(stream_pokemon_info_translated)
+                .app_data(Data::new(Pokedex::new(
+                    PokeApiClient::new(mock_server.uri(), Duration::from_millis(200)),
+                    Duration::from_secs(60),
+                    Duration::from_secs(60),
+                )))
+                .app_data(Data::new(TranslationService::new(
+                    TranslatorChain::new(
+                        TranslatorRegistry::new(
+                            vec![Arc::new(FunTranslationsApiClient::new(
+                                mock_server.uri(),
+                                Duration::from_millis(200),
+                            ))],
+                            &HashMap::new(),
+                        )
+                        .unwrap(),
+                        vec![],
+                        HealthPolicy {
+                            failure_threshold: 3,
+                            cooldown: Duration::from_secs(60),
+                        },
+                        HashMap::new(),
+                    )
+                    .unwrap(),
+                    TtlCache::new(Duration::from_secs(60), 10),
+                )))
+                .app_data(Data::new(TranslationJobs::disabled())),
+        )
+        .await;
+
+        // Act
+        let response = test::call_service(
+            &app,
+            test::TestRequest::post()
+                .uri("/pokemon/translated/stream")
+                .set_json(&json!({"names": ["mewtwo", "missingno"]}))
+                .to_request(),
+        )
+        .await;
+
+        // Assert
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            "text/event-stream"
+        );
+        let body = String::from_utf8(test::read_body(response).await.to_vec()).unwrap();
+        let events = body
+            .split_terminator("\n\n")
+            .map(|event| {
+                let (kind, data) = event.split_once('\n').unwrap();
+                let data = serde_json::from_str::<Value>(data.trim_start_matches("data: "));
+                (kind.trim_start_matches("event: "), data.unwrap())
+            })
+            .collect::<Vec<(&str, Value)>>();
+        let position = |kind: &str| events.iter().position(|(k, _)| *k == kind).unwrap();
+        assert_eq!(events.len(), 4);
+        assert_eq!(
+            events[position("info")].1["description"],
+            "It was created by a scientist."
+        );
+        assert_eq!(
+            events[position("translation")].1["description"],
+            "Created by a scientist, it was."
+        );
+        assert!(position("info") < position("translation"));
+        assert_eq!(events[position("error")].1["name"], "missingno");
+        assert_eq!(events[position("error")].1["code"], "PE_NOT_FOUND");
+        assert_eq!(events[3].0, "done");
+    }
+}
@@ -0,0 +1,35 @@
+use actix_web::{get, web, HttpResponse};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::jobs::warmup::{Warmup, WarmupProgress};
+
+#[derive(Serialize, ToSchema)]
+pub struct Readiness {
+    ready: bool,
+    warmup: WarmupProgress,
+}
+
+/// 503 until the startup warmup is over, so that no traffic is routed to an
+/// instance with cold caches.
+#[utoipa::path(
+    get,
+    path = "/ready",
+    tag = "health",
+    responses(
+        (status = 200, description = "Ready to serve traffic", body = Readiness),
+        (status = 503, description = "Still warming up", body = Readiness),
+    )
+)]
+#[get("/ready")]
+pub async fn ready(warmup: web::Data<Warmup>) -> HttpResponse {
+    let readiness = Readiness {
+        ready: warmup.is_ready(),
+        warmup: warmup.progress(),
+    };
+    if readiness.ready {
+        HttpResponse::Ok().json(readiness)
+    } else {
+        HttpResponse::ServiceUnavailable().json(readiness)
+    }
+}
@@ -0,0 +1,186 @@
+use actix_web::{get, post, web, HttpResponse};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::configuration::Settings;
+use crate::error::PokeError;
+use crate::routes::caching::mark_staleness;
+use crate::routes::pokemon::{TRANSLATION_ATTEMPTS_HEADER, TRANSLATION_ENGINE_HEADER};
+use crate::translation::service::TranslationService;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct DialectParams {
+    dialect: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TranslateRequest {
+    text: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/translate/dialects",
+    context_path = "/v1",
+    tag = "translate",
+    responses((status = 200, description = "Supported dialects and the provider serving each", body = [DialectInfo]))
+)]
+#[get("/translate/dialects")]
+pub async fn list_dialects(
+    translation_service: web::Data<TranslationService>,
+) -> Result<HttpResponse, PokeError> {
+    Ok(HttpResponse::Ok().json(translation_service.dialects()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/translate/providers",
+    context_path = "/v1",
+    tag = "translate",
+    responses((status = 200, description = "Health of the translation providers", body = [ProviderHealth]))
+)]
+#[get("/translate/providers")]
+pub async fn list_providers(
+    translation_service: web::Data<TranslationService>,
+) -> Result<HttpResponse, PokeError> {
+    Ok(HttpResponse::Ok().json(translation_service.providers()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/translate/{dialect}",
+    context_path = "/v1",
+    tag = "translate",
+    params(DialectParams),
+    request_body = TranslateRequest,
+    responses(
+        (status = 200, description = "The translated text", body = Translation, headers(
+            ("X-Translation-Engine" = String, description = "Provider that translated the text"),
+            ("X-Translation-Attempts" = u32, description = "Providers called, zero for cached translations"),
+        )),
+        (status = 400, description = "PE_BAD_REQUEST: empty or too long text, or unsupported dialect", body = PokeError),
+        (status = 404, description = "PE_NOT_FOUND", body = PokeError),
+        (status = 429, description = "PE_RATE_LIMITED or PE_QUOTA_EXHAUSTED", body = PokeError),
+        (status = 503, description = "PE_UNAVAILABLE", body = PokeError),
+        (status = 504, description = "PE_TIMEOUT", body = PokeError),
+        (status = 500, description = "PE_INTERNAL", body = PokeError),
+    )
+)]
+#[post("/translate/{dialect}")]
+pub async fn translate(
+    params: web::Path<DialectParams>,
+    request: web::Json<TranslateRequest>,
+    translation_service: web::Data<TranslationService>,
+    settings: web::Data<Settings>,
+) -> Result<HttpResponse, PokeError> {
+    let text = request.text.trim();
+    if text.is_empty() {
+        return Err(PokeError::bad_request(String::from(
+            "text must not be empty",
+        )));
+    }
+    if text.chars().count() > settings.translation_max_text_length {
+        return Err(PokeError::bad_request(format!(
+            "text must be at most {} characters long",
+            settings.translation_max_text_length
+        )));
+    }
+    let chained = translation_service
+        .translate(&params.dialect.to_lowercase(), text)
+        .await?;
+    let mut response = HttpResponse::Ok();
+    response
+        .insert_header((
+            TRANSLATION_ENGINE_HEADER,
+            chained.translation.engine.as_str(),
+        ))
+        .insert_header((TRANSLATION_ATTEMPTS_HEADER, chained.attempts.to_string()));
+    mark_staleness(&mut response, chained.staleness);
+    Ok(response.json(chained.translation))
+}
+
+/// Caps json payloads well above the text limit (a char is at most four
+/// bytes) and reports malformed bodies with the usual error format.
+pub fn json_config(max_text_length: usize) -> web::JsonConfig {
+    web::JsonConfig::default()
+        .limit(max_text_length * 4 + 1024)
+        .error_handler(|error, _| PokeError::bad_request(error.to_string()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc, time::Duration};
+
+    use actix_web::{http::StatusCode, test, web::Data, App};
+    use serde_json::{json, Value};
+    use wiremock::{matchers::any, Mock, MockServer, ResponseTemplate};
+
+    use crate::{
+        cache::memory::TtlCache,
+        configuration::Settings,
+        funtranslations_api::client::FunTranslationsApiClient,
+        routes::translate::{json_config, translate},
+        translation::{
+            chain::{HealthPolicy, TranslatorChain},
+            registry::TranslatorRegistry,
+            service::TranslationService,
+        },
+    };
+
+    #[actix_rt::test]
+    async fn translate_rejects_invalid_texts_without_calling_the_server() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+        let settings = Settings::from_vars(&|_| None);
+        let app = test::init_service(
+            App::new()
+                .service(translate)
+                .app_data(json_config(settings.translation_max_text_length))
+                .app_data(Data::new(TranslationService::new(
+                    TranslatorChain::new(
+                        TranslatorRegistry::new(
+                            vec![Arc::new(FunTranslationsApiClient::new(
+                                mock_server.uri(),
+                                Duration::from_millis(200),
+                            ))],
+                            &HashMap::new(),
+                        )
+                        .unwrap(),
+                        vec![],
+                        HealthPolicy {
+                            failure_threshold: 3,
+                            cooldown: Duration::from_secs(60),
+                        },
+                        HashMap::new(),
+                    )
+                    .unwrap(),
+                    TtlCache::new(Duration::from_secs(60), 10),
+                )))
+                .app_data(Data::new(settings)),
+        )
+        .await;
+
+        for body in &[
+            json!({"text": "   "}),
+            json!({"text": "a".repeat(1001)}),
+            json!({"no_text": "here"}),
+        ] {
+            // Act
+            let request = test::TestRequest::post()
+                .uri("/translate/yoda")
+                .set_json(body)
+                .to_request();
+            let response = test::call_service(&app, request).await;
+
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let error: Value = test::read_body_json(response).await;
+            assert_eq!(error["code"], "PE_BAD_REQUEST");
+        }
+    }
+}
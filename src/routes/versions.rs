@@ -0,0 +1,139 @@
+use std::rc::Rc;
+
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderName, HeaderValue, LINK},
+    web, Error,
+};
+use futures::future::{ready, LocalBoxFuture, Ready};
+
+use crate::routes::{pokemon, pokemon_v2, translate};
+
+/// Routes of v1, whose responses keep the shape they had when it was frozen.
+pub fn v1(cfg: &mut web::ServiceConfig) {
+    cfg.service(pokemon::list_pokemon)
+        .service(pokemon::get_random_pokemon_info)
+        .service(pokemon::get_daily_pokemon_info)
+        .service(pokemon::get_random_pokemon_info_translated)
+        .service(pokemon::get_daily_pokemon_info_translated)
+        .service(pokemon::get_pokemon_info)
+        .service(pokemon::get_pokemon_info_translated)
+        .service(translate::list_dialects)
+        .service(translate::list_providers)
+        .service(translate::translate);
+}
+
+/// Routes of v2. The translate routes have not changed, so they are only
+/// served by v1.
+pub fn v2(cfg: &mut web::ServiceConfig) {
+    cfg.service(pokemon_v2::list_pokemon)
+        .service(pokemon_v2::get_random_pokemon_info)
+        .service(pokemon_v2::get_daily_pokemon_info)
+        .service(pokemon_v2::get_random_pokemon_info_translated)
+        .service(pokemon_v2::get_daily_pokemon_info_translated)
+        .service(pokemon_v2::stream_pokemon_info_translated)
+        .service(pokemon_v2::get_pokemon_info)
+        .service(pokemon_v2::get_pokemon_info_translated);
+}
+
+/// Middleware marking the unversioned aliases of v1 as deprecated, linking
+/// each response to the same path under `/v1`.
+pub struct Deprecation;
+
+impl<S, B> Transform<S, ServiceRequest> for Deprecation
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = DeprecationMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(DeprecationMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct DeprecationMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for DeprecationMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    actix_web::dev::forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let successor = format!("</v1{}>; rel=\"successor-version\"", request.path());
+        let service = self.service.clone();
+        Box::pin(async move {
+            let mut response = service.call(request).await?;
+            let headers = response.headers_mut();
+            headers.insert(
+                HeaderName::from_static("deprecation"),
+                HeaderValue::from_static("true"),
+            );
+            if let Ok(link) = HeaderValue::from_str(&successor) {
+                headers.insert(LINK, link);
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{get, http::StatusCode, test, web, App, HttpResponse};
+
+    use crate::routes::versions::Deprecation;
+
+    #[get("/pokemon/{name}")]
+    async fn pokemon() -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    #[actix_rt::test]
+    async fn only_unversioned_aliases_are_marked_deprecated() {
+        // Arrange
+        let app = test::init_service(
+            App::new()
+                .service(web::scope("/v1").service(pokemon))
+                .service(web::scope("").wrap(Deprecation).service(pokemon)),
+        )
+        .await;
+
+        // Act
+        let versioned = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/v1/pokemon/mewtwo")
+                .to_request(),
+        )
+        .await;
+        let unversioned = test::call_service(
+            &app,
+            test::TestRequest::get().uri("/pokemon/mewtwo").to_request(),
+        )
+        .await;
+
+        // Assert
+        assert_eq!(versioned.status(), StatusCode::OK);
+        assert!(versioned.headers().get("deprecation").is_none());
+        assert_eq!(unversioned.status(), StatusCode::OK);
+        assert_eq!(unversioned.headers().get("deprecation").unwrap(), "true");
+        assert_eq!(
+            unversioned.headers().get("link").unwrap(),
+            "</v1/pokemon/mewtwo>; rel=\"successor-version\""
+        );
+    }
+}
@@ -0,0 +1,514 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::cache::stale::Staleness;
+use crate::translation::registry::TranslatorRegistry;
+use crate::translation::translator::{Translation, TranslationError, Translator};
+
+/// Consecutive failures after which a provider is skipped for `cooldown`.
+#[derive(Debug, Clone)]
+pub struct HealthPolicy {
+    pub failure_threshold: u32,
+    pub cooldown: Duration,
+}
+
+/// At most `limit` requests to a provider per `window`.
+#[derive(Debug, Clone)]
+pub struct Quota {
+    pub limit: u32,
+    pub window: Duration,
+}
+
+#[derive(Debug)]
+pub struct ChainedTranslation {
+    pub translation: Translation,
+    /// Providers actually called, including the one that answered.
+    pub attempts: u32,
+    /// Whether the answer came from the first provider in the chain.
+    pub preferred: bool,
+    /// Whether the answer is an expired cache entry.
+    pub staleness: Staleness,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ProviderHealth {
+    pub provider: String,
+    pub healthy: bool,
+    pub consecutive_failures: u32,
+    pub successes: u64,
+    pub failures: u64,
+    pub quota_remaining: Option<u32>,
+}
+
+struct ProviderState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    successes: u64,
+    failures: u64,
+    window_start: Instant,
+    window_count: u32,
+}
+
+impl ProviderState {
+    fn new() -> Self {
+        Self {
+            consecutive_failures: 0,
+            open_until: None,
+            successes: 0,
+            failures: 0,
+            window_start: Instant::now(),
+            window_count: 0,
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.open_until
+            .is_some_and(|open_until| Instant::now() < open_until)
+    }
+
+    fn quota_remaining(&mut self, quota: &Quota) -> u32 {
+        if self.window_start.elapsed() >= quota.window {
+            self.window_start = Instant::now();
+            self.window_count = 0;
+        }
+        quota.limit.saturating_sub(self.window_count)
+    }
+}
+
+/// Tries the providers able to serve a dialect in order, moving on to the
+/// next one when a provider is rate limited, times out or fails upstream.
+/// The provider routed to the dialect always comes first, followed by the
+/// configured order.
+pub struct TranslatorChain {
+    registry: TranslatorRegistry,
+    order: Vec<String>,
+    policy: HealthPolicy,
+    quotas: HashMap<String, Quota>,
+    states: Mutex<HashMap<String, ProviderState>>,
+}
+
+impl TranslatorChain {
+    pub fn new(
+        registry: TranslatorRegistry,
+        order: Vec<String>,
+        policy: HealthPolicy,
+        quotas: HashMap<String, Quota>,
+    ) -> Result<Self, String> {
+        if let Some(unknown) = order
+            .iter()
+            .chain(quotas.keys())
+            .find(|name| registry.get(name).is_none())
+        {
+            return Err(format!("unknown translation provider '{}'", unknown));
+        }
+        Ok(Self {
+            registry,
+            order,
+            policy,
+            quotas,
+            states: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn registry(&self) -> &TranslatorRegistry {
+        &self.registry
+    }
+
+    pub fn candidates(&self, dialect: &str) -> Vec<Arc<dyn Translator>> {
+        let mut candidates: Vec<Arc<dyn Translator>> = Vec::new();
+        let routed = self.registry.translator_for(dialect).into_iter().cloned();
+        let ordered = self
+            .order
+            .iter()
+            .filter_map(|name| self.registry.get(name))
+            .filter(|translator| {
+                translator
+                    .supported_dialects()
+                    .iter()
+                    .any(|supported| supported == dialect)
+            })
+            .cloned();
+        for translator in routed.chain(ordered) {
+            if candidates
+                .iter()
+                .all(|candidate| candidate.name() != translator.name())
+            {
+                candidates.push(translator);
+            }
+        }
+        candidates
+    }
+
+    pub async fn translate(
+        &self,
+        dialect: &str,
+        text: &str,
+    ) -> Result<ChainedTranslation, TranslationError> {
+        let candidates = self.candidates(dialect);
+        if candidates.is_empty() {
+            return Err(TranslationError::UnsupportedDialect {
+                dialect: String::from(dialect),
+            });
+        }
+        let mut attempts = 0;
+        let mut last_error = None;
+        for (position, translator) in candidates.iter().enumerate() {
+            if !self.acquire(translator.name()) {
+                continue;
+            }
+            attempts += 1;
+            match translator.translate(dialect, text).await {
+                Ok(translation) => {
+                    self.record(translator.name(), true);
+                    return Ok(ChainedTranslation {
+                        translation,
+                        attempts,
+                        preferred: position == 0,
+                        staleness: Staleness::Fresh,
+                    });
+                }
+                // Refused by the provider's own budget before any request
+                // was sent: skipped, as when the configured quota is spent.
+                Err(TranslationError::QuotaExhausted) => {
+                    self.release(translator.name());
+                    attempts -= 1;
+                    last_error = Some(TranslationError::QuotaExhausted);
+                }
+                Err(error) if is_retryable(&error) => {
+                    self.record(translator.name(), false);
+                    last_error = Some(error);
+                }
+                Err(error) => return Err(error),
+            }
+        }
+        Err(last_error.unwrap_or(TranslationError::Unavailable))
+    }
+
+    pub fn health(&self) -> Vec<ProviderHealth> {
+        let mut states = self.states.lock().unwrap();
+        self.registry
+            .names()
+            .into_iter()
+            .map(|name| {
+                let state = states
+                    .entry(String::from(name))
+                    .or_insert_with(ProviderState::new);
+                ProviderHealth {
+                    provider: String::from(name),
+                    healthy: !state.is_open(),
+                    consecutive_failures: state.consecutive_failures,
+                    successes: state.successes,
+                    failures: state.failures,
+                    quota_remaining: self
+                        .quotas
+                        .get(name)
+                        .map(|quota| state.quota_remaining(quota)),
+                }
+            })
+            .collect()
+    }
+
+    /// Whether `translator` may be called now while leaving `reserve` calls
+    /// of both the quota configured for it and the budget it tracks itself.
+    /// Nothing is counted.
+    pub fn has_budget(&self, translator: &dyn Translator, reserve: u32) -> bool {
+        let mut states = self.states.lock().unwrap();
+        let state = states
+            .entry(String::from(translator.name()))
+            .or_insert_with(ProviderState::new);
+        if state.is_open() {
+            return false;
+        }
+        let configured = self
+            .quotas
+            .get(translator.name())
+            .is_none_or(|quota| state.quota_remaining(quota) > reserve);
+        let tracked = translator.quota().is_none_or(|quota| {
+            quota.hourly_remaining > reserve && quota.daily_remaining > reserve
+        });
+        configured && tracked
+    }
+
+    /// Whether the provider may be called now, counting the call against its
+    /// quota if it may.
+    fn acquire(&self, name: &str) -> bool {
+        let mut states = self.states.lock().unwrap();
+        let state = states
+            .entry(String::from(name))
+            .or_insert_with(ProviderState::new);
+        if state.is_open() {
+            return false;
+        }
+        if let Some(quota) = self.quotas.get(name) {
+            if state.quota_remaining(quota) == 0 {
+                return false;
+            }
+            state.window_count += 1;
+        }
+        true
+    }
+
+    /// Gives back the quota counted by `acquire` for a call never made.
+    fn release(&self, name: &str) {
+        if self.quotas.contains_key(name) {
+            let mut states = self.states.lock().unwrap();
+            if let Some(state) = states.get_mut(name) {
+                state.window_count = state.window_count.saturating_sub(1);
+            }
+        }
+    }
+
+    fn record(&self, name: &str, success: bool) {
+        let mut states = self.states.lock().unwrap();
+        let state = states
+            .entry(String::from(name))
+            .or_insert_with(ProviderState::new);
+        if success {
+            state.successes += 1;
+            state.consecutive_failures = 0;
+            state.open_until = None;
+        } else {
+            state.failures += 1;
+            state.consecutive_failures += 1;
+            if state.consecutive_failures >= self.policy.failure_threshold {
+                state.open_until = Some(Instant::now() + self.policy.cooldown);
+            }
+        }
+    }
+}
+
+/// Whether the error is down to the provider rather than the request, so
+/// that another provider, or a later attempt, may succeed.
+pub fn is_retryable(error: &TranslationError) -> bool {
+    matches!(
+        error,
+        TranslationError::RateLimited
+            | TranslationError::QuotaExhausted
+            | TranslationError::Timeout
+            | TranslationError::InternalError
+            | TranslationError::Unavailable
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc, time::Duration};
+
+    use claim::{assert_err, assert_ok};
+    use wiremock::{
+        matchers::{any, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use crate::{
+        funtranslations_api::{client::FunTranslationsApiClient, quota::QuotaLimits},
+        translation::{
+            chain::{HealthPolicy, Quota, TranslatorChain},
+            local::LocalTranslator,
+            registry::TranslatorRegistry,
+            translator::{TranslationError, Translator},
+        },
+    };
+
+    fn chain(base_url: String, quotas: HashMap<String, Quota>) -> TranslatorChain {
+        let translators: Vec<Arc<dyn Translator>> = vec![
+            Arc::new(FunTranslationsApiClient::new(
+                base_url,
+                Duration::from_millis(200),
+            )),
+            Arc::new(LocalTranslator),
+        ];
+        TranslatorChain::new(
+            TranslatorRegistry::new(translators, &HashMap::new()).unwrap(),
+            vec![String::from("funtranslations"), String::from("local")],
+            HealthPolicy {
+                failure_threshold: 2,
+                cooldown: Duration::from_secs(60),
+            },
+            quotas,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn translate_moves_to_the_next_provider_on_retryable_errors() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let chain = chain(mock_server.uri(), HashMap::new());
+        Mock::given(path("/translate/yoda.json"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(429))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let chained = chain.translate("yoda", "You are strong.").await;
+
+        assert_ok!(&chained);
+        let chained = chained.unwrap();
+        assert_eq!(chained.translation.engine, "local");
+        assert_eq!(chained.attempts, 2);
+        assert!(!chained.preferred);
+    }
+
+    #[tokio::test]
+    async fn translate_stops_at_errors_caused_by_the_request() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let chain = chain(mock_server.uri(), HashMap::new());
+        Mock::given(path("/translate/yoda.json"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let chained = chain.translate("yoda", "You are strong.").await;
+
+        assert_err!(&chained);
+        assert_eq!(chained.unwrap_err(), TranslationError::NotFound);
+    }
+
+    #[tokio::test]
+    async fn translate_skips_providers_that_keep_failing() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let chain = chain(mock_server.uri(), HashMap::new());
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        for _ in 0..4 {
+            let chained = chain.translate("yoda", "You are strong.").await;
+            assert_ok!(&chained);
+        }
+
+        let health = chain.health();
+        let funtranslations = health
+            .iter()
+            .find(|health| health.provider == "funtranslations")
+            .unwrap();
+        assert!(!funtranslations.healthy);
+        assert_eq!(funtranslations.failures, 2);
+    }
+
+    #[tokio::test]
+    async fn has_budget_accounts_for_quotas_and_health() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let mut quotas = HashMap::new();
+        quotas.insert(
+            String::from("funtranslations"),
+            Quota {
+                limit: 3,
+                window: Duration::from_secs(60),
+            },
+        );
+        let chain = chain(mock_server.uri(), quotas);
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&mock_server)
+            .await;
+        let funtranslations = chain.registry().get("funtranslations").unwrap().clone();
+
+        // Act
+        let before = chain.has_budget(funtranslations.as_ref(), 2);
+        let _ = chain.translate("yoda", "You are strong.").await;
+        let within_reserve = chain.has_budget(funtranslations.as_ref(), 2);
+        let without_reserve = chain.has_budget(funtranslations.as_ref(), 0);
+        let _ = chain.translate("yoda", "You are strong.").await;
+        let unhealthy = chain.has_budget(funtranslations.as_ref(), 0);
+
+        // Assert
+        assert!(before);
+        assert!(!within_reserve);
+        assert!(without_reserve);
+        assert!(!unhealthy);
+    }
+
+    #[tokio::test]
+    async fn translate_skips_providers_without_quota_left() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let mut quotas = HashMap::new();
+        quotas.insert(
+            String::from("funtranslations"),
+            Quota {
+                limit: 0,
+                window: Duration::from_secs(60),
+            },
+        );
+        let chain = chain(mock_server.uri(), quotas);
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let chained = chain.translate("yoda", "You are strong.").await;
+
+        assert_ok!(&chained);
+        let chained = chained.unwrap();
+        assert_eq!(chained.translation.engine, "local");
+        assert_eq!(chained.attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn translate_does_not_count_local_quota_refusals_as_failures() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let translators: Vec<Arc<dyn Translator>> = vec![
+            Arc::new(
+                FunTranslationsApiClient::new(mock_server.uri(), Duration::from_millis(200))
+                    .with_quota(QuotaLimits {
+                        hourly: 0,
+                        daily: 0,
+                    }),
+            ),
+            Arc::new(LocalTranslator),
+        ];
+        let chain = TranslatorChain::new(
+            TranslatorRegistry::new(translators, &HashMap::new()).unwrap(),
+            vec![String::from("funtranslations"), String::from("local")],
+            HealthPolicy {
+                failure_threshold: 2,
+                cooldown: Duration::from_secs(60),
+            },
+            HashMap::new(),
+        )
+        .unwrap();
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        for _ in 0..3 {
+            let chained = chain.translate("yoda", "You are strong.").await;
+            assert_ok!(&chained);
+            assert_eq!(chained.unwrap().attempts, 1);
+        }
+
+        // Assert
+        let health = chain.health();
+        let funtranslations = health
+            .iter()
+            .find(|health| health.provider == "funtranslations")
+            .unwrap();
+        assert!(funtranslations.healthy);
+        assert_eq!(funtranslations.failures, 0);
+    }
+}
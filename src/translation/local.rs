@@ -0,0 +1,343 @@
+use async_trait::async_trait;
+
+use crate::translation::translator::{Capabilities, Translation, TranslationError, Translator};
+
+/// Auxiliaries after which Yoda moves the rest of the sentence to the front.
+const YODA_AUXILIARIES: [&str; 20] = [
+    "is", "are", "was", "were", "am", "has", "have", "had", "can", "could", "will", "would",
+    "shall", "should", "must", "may", "might", "does", "do", "did",
+];
+
+/// How far into a sentence the auxiliary is looked for; past this the
+/// subject is too long for the reordering to read naturally.
+const YODA_MAX_SUBJECT_WORDS: usize = 4;
+
+/// Second person forms are conjugated with their subject: "you are" becomes
+/// "thou art", while "you" alone is taken as an object, "thee", unless it
+/// starts a sentence.
+const SHAKESPEARE_LEXICON: [(&str, &str); 34] = [
+    ("you are", "thou art"),
+    ("you were", "thou wert"),
+    ("you have", "thou hast"),
+    ("you do", "thou dost"),
+    ("you can", "thou canst"),
+    ("you will", "thou wilt"),
+    ("you shall", "thou shalt"),
+    ("you", "thee"),
+    ("your", "thy"),
+    ("yours", "thine"),
+    ("yourself", "thyself"),
+    ("has", "hath"),
+    ("does", "doth"),
+    ("hello", "good morrow"),
+    ("hi", "hail"),
+    ("yes", "aye"),
+    ("no", "nay"),
+    ("before", "ere"),
+    ("often", "oft"),
+    ("here", "hither"),
+    ("there", "thither"),
+    ("why", "wherefore"),
+    ("very", "most"),
+    ("maybe", "perchance"),
+    ("perhaps", "perchance"),
+    ("friend", "sirrah"),
+    ("enemy", "foe"),
+    ("over", "o'er"),
+    ("never", "ne'er"),
+    ("even", "e'en"),
+    ("it is", "'tis"),
+    ("it was", "'twas"),
+    ("nothing", "naught"),
+    ("anything", "aught"),
+];
+
+/// Third person pronouns after which a verb takes the `-eth` ending.
+const SHAKESPEARE_SUBJECTS: [&str; 3] = ["he", "she", "it"];
+
+/// Offline translator for the two dialects used for Pokémon descriptions,
+/// based on word order and substitution heuristics rather than a model.
+pub struct LocalTranslator;
+
+#[async_trait]
+impl Translator for LocalTranslator {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    async fn translate(&self, dialect: &str, text: &str) -> Result<Translation, TranslationError> {
+        let translated = match dialect {
+            "yoda" => yodify(text),
+            "shakespeare" => shakespearify(text),
+            _ => {
+                return Err(TranslationError::UnsupportedDialect {
+                    dialect: String::from(dialect),
+                })
+            }
+        };
+        Ok(Translation {
+            dialect: String::from(dialect),
+            original: String::from(text),
+            translated,
+            engine: String::from(self.name()),
+        })
+    }
+
+    fn supported_dialects(&self) -> Vec<String> {
+        vec![String::from("yoda"), String::from("shakespeare")]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            remote: false,
+            rate_limited: false,
+            max_text_length: None,
+        }
+    }
+}
+
+/// Splits text into sentences, keeping each terminator with its sentence.
+fn sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        if matches!(c, '.' | '!' | '?') {
+            sentences.push(&text[start..i + c.len_utf8()]);
+            start = i + c.len_utf8();
+        }
+    }
+    if start < text.len() {
+        sentences.push(&text[start..]);
+    }
+    sentences
+        .into_iter()
+        .map(str::trim)
+        .filter(|sentence| !sentence.is_empty())
+        .collect()
+}
+
+/// Object-subject-verb reordering: "Master Obiwan has lost a planet." becomes
+/// "Lost a planet, master Obiwan has."
+fn yodify(text: &str) -> String {
+    sentences(text)
+        .into_iter()
+        .map(yodify_sentence)
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn yodify_sentence(sentence: &str) -> String {
+    let (body, terminator) = match sentence.char_indices().last() {
+        Some((i, '.' | '!' | '?')) => (&sentence[..i], &sentence[i..]),
+        _ => (sentence, "."),
+    };
+    let words = body.split_whitespace().collect::<Vec<&str>>();
+    let split = words
+        .iter()
+        .take(YODA_MAX_SUBJECT_WORDS + 1)
+        .position(|word| YODA_AUXILIARIES.contains(&word.to_lowercase().as_str()))
+        .filter(|i| *i > 0 && i + 1 < words.len());
+    let split = match split {
+        Some(split) => split,
+        None if words.len() >= 3 => 1,
+        None => return String::from(sentence),
+    };
+    let subject = decapitalize(&words[..split].join(" "));
+    let verb = words[split];
+    let object = words[split + 1..].join(" ");
+    format!(
+        "{}, {} {}{}",
+        capitalize(object.trim_end_matches(',')),
+        subject,
+        verb,
+        terminator
+    )
+}
+
+/// Lexicon substitutions plus the `-eth` ending after third person pronouns:
+/// "It stores energy." becomes "It storeth energy."
+fn shakespearify(text: &str) -> String {
+    let mut words = text
+        .split_whitespace()
+        .map(String::from)
+        .collect::<Vec<String>>();
+    let mut i = 0;
+    while i < words.len() {
+        let word = words[i].clone();
+        let (prefix, core, suffix) = split_punctuation(&word);
+        let lower = core.to_lowercase();
+        if i + 1 < words.len() {
+            let next = words[i + 1].clone();
+            let (next_prefix, next_core, next_suffix) = split_punctuation(&next);
+            let pair = format!("{} {}", lower, next_core.to_lowercase());
+            if suffix.is_empty() {
+                if let Some((_, archaic)) = SHAKESPEARE_LEXICON.iter().find(|(w, _)| *w == pair) {
+                    words[i] = format!("{}{}{}", prefix, match_case(core, archaic), next_suffix);
+                    words.remove(i + 1);
+                    i += 1;
+                    continue;
+                }
+                if SHAKESPEARE_SUBJECTS.contains(&lower.as_str()) {
+                    if let Some(verb) = eth(next_core) {
+                        words[i + 1] = format!("{}{}{}", next_prefix, verb, next_suffix);
+                    }
+                }
+            }
+        }
+        let starts_sentence = i == 0 || words[i - 1].ends_with(['.', '!', '?']);
+        if lower == "you" && starts_sentence {
+            words[i] = format!("{}{}{}", prefix, match_case(core, "thou"), suffix);
+            if suffix.is_empty() && i + 1 < words.len() {
+                let next = words[i + 1].clone();
+                let (next_prefix, next_core, next_suffix) = split_punctuation(&next);
+                if let Some(verb) = est(next_core) {
+                    words[i + 1] = format!("{}{}{}", next_prefix, verb, next_suffix);
+                }
+            }
+        } else if let Some((_, archaic)) = SHAKESPEARE_LEXICON.iter().find(|(w, _)| *w == lower) {
+            words[i] = format!("{}{}{}", prefix, match_case(core, archaic), suffix);
+        }
+        i += 1;
+    }
+    words.join(" ")
+}
+
+/// "uses" becomes "useth" and "runs" becomes "runeth"; words that are not a
+/// plain third person `-s` verb are left alone.
+fn eth(word: &str) -> Option<String> {
+    let lower = word.to_lowercase();
+    if lower.len() < 4
+        || !lower.ends_with('s')
+        || lower.ends_with("ss")
+        || YODA_AUXILIARIES.contains(&lower.as_str())
+        || !lower.chars().all(|c| c.is_ascii_alphabetic())
+    {
+        return None;
+    }
+    let stem = &word[..word.len() - 1];
+    if stem.ends_with('e') {
+        Some(format!("{}th", stem))
+    } else {
+        Some(format!("{}eth", stem))
+    }
+}
+
+/// "see" becomes "seest" and "know" becomes "knowest", for a verb following
+/// "thou"; words that do not look like a bare verb are left alone.
+fn est(word: &str) -> Option<String> {
+    if word.len() < 2
+        || YODA_AUXILIARIES.contains(&word)
+        || !word.chars().all(|c| c.is_ascii_lowercase())
+        || ["s", "ed", "ing", "ly"]
+            .iter()
+            .any(|ending| word.ends_with(ending))
+    {
+        return None;
+    }
+    if word.ends_with('e') {
+        Some(format!("{}st", word))
+    } else {
+        Some(format!("{}est", word))
+    }
+}
+
+fn split_punctuation(word: &str) -> (&str, &str, &str) {
+    let start = word
+        .find(|c: char| c.is_alphanumeric())
+        .unwrap_or(word.len());
+    let end = word
+        .rfind(|c: char| c.is_alphanumeric())
+        .map_or(start, |i| i + 1);
+    (&word[..start], &word[start..end], &word[end..])
+}
+
+fn match_case(original: &str, replacement: &str) -> String {
+    if original.starts_with(char::is_uppercase) {
+        capitalize(replacement)
+    } else {
+        String::from(replacement)
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Lowercases the first letter unless the word looks like a name or acronym,
+/// i.e. its second letter is uppercase too, or it is "I".
+fn decapitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(first), Some(second)) if !second.is_uppercase() && second.is_alphabetic() => {
+            first.to_lowercase().chain(text.chars().skip(1)).collect()
+        }
+        _ => String::from(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::assert_err;
+
+    use crate::translation::{
+        local::{shakespearify, yodify, LocalTranslator},
+        translator::{TranslationError, Translator},
+    };
+
+    #[test]
+    fn yodify_moves_the_object_before_subject_and_verb() {
+        assert_eq!(
+            yodify("Master Obiwan has lost a planet."),
+            "Lost a planet, master Obiwan has."
+        );
+        assert_eq!(
+            yodify("It was created by a scientist. It is very strong!"),
+            "Created by a scientist, it was. Very strong, it is!"
+        );
+    }
+
+    #[test]
+    fn yodify_leaves_short_sentences_alone() {
+        assert_eq!(yodify("Run away."), "Run away.");
+    }
+
+    #[test]
+    fn shakespearify_substitutes_the_lexicon_and_conjugates_verbs() {
+        assert_eq!(
+            shakespearify("It stores energy. You are very brave, and it is here."),
+            "It storeth energy. Thou art most brave, and 'tis hither."
+        );
+        assert_eq!(
+            shakespearify("You see it. It sees you, and they are near."),
+            "Thou seest it. It seeth thee, and they are near."
+        );
+        assert_eq!(
+            shakespearify("You quickly run. You know."),
+            "Thou quickly run. Thou knowest."
+        );
+    }
+
+    #[tokio::test]
+    async fn translate_reports_the_local_engine() {
+        let translation = LocalTranslator.translate("yoda", "You are strong.").await;
+
+        assert_eq!(translation.unwrap().engine, "local");
+    }
+
+    #[tokio::test]
+    async fn translate_fails_for_other_dialects() {
+        let translation = LocalTranslator.translate("pirate", "Hello").await;
+
+        assert_err!(&translation);
+        assert_eq!(
+            translation.unwrap_err(),
+            TranslationError::UnsupportedDialect {
+                dialect: String::from("pirate")
+            }
+        );
+    }
+}
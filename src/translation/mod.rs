@@ -0,0 +1,5 @@
+pub mod chain;
+pub mod local;
+pub mod registry;
+pub mod service;
+pub mod translator;
@@ -0,0 +1,149 @@
+use std::{collections::HashMap, sync::Arc};
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::translation::translator::{Capabilities, Translator};
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct DialectInfo {
+    pub dialect: String,
+    pub provider: String,
+    pub capabilities: Capabilities,
+}
+
+/// Decides which backend serves which dialect. Explicit routes win; any
+/// other dialect goes to the first backend listing it as supported.
+pub struct TranslatorRegistry {
+    translators: Vec<Arc<dyn Translator>>,
+    routes: HashMap<String, Arc<dyn Translator>>,
+}
+
+impl TranslatorRegistry {
+    pub fn new(
+        translators: Vec<Arc<dyn Translator>>,
+        routes: &HashMap<String, String>,
+    ) -> Result<Self, String> {
+        let mut routed = HashMap::new();
+        for (dialect, provider) in routes {
+            let translator = translators
+                .iter()
+                .find(|translator| translator.name() == provider)
+                .ok_or_else(|| {
+                    format!(
+                        "dialect '{}' is routed to unknown provider '{}'",
+                        dialect, provider
+                    )
+                })?;
+            routed.insert(dialect.clone(), translator.clone());
+        }
+        Ok(Self {
+            translators,
+            routes: routed,
+        })
+    }
+
+    pub fn translators(&self) -> &[Arc<dyn Translator>] {
+        &self.translators
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.translators
+            .iter()
+            .map(|translator| translator.name())
+            .collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn Translator>> {
+        self.translators
+            .iter()
+            .find(|translator| translator.name() == name)
+    }
+
+    pub fn translator_for(&self, dialect: &str) -> Option<&Arc<dyn Translator>> {
+        self.routes.get(dialect).or_else(|| {
+            self.translators.iter().find(|translator| {
+                translator
+                    .supported_dialects()
+                    .iter()
+                    .any(|supported| supported == dialect)
+            })
+        })
+    }
+
+    pub fn dialects(&self) -> Vec<DialectInfo> {
+        let mut dialects = self
+            .translators
+            .iter()
+            .flat_map(|translator| translator.supported_dialects())
+            .chain(self.routes.keys().cloned())
+            .collect::<Vec<String>>();
+        dialects.sort();
+        dialects.dedup();
+        dialects
+            .into_iter()
+            .filter_map(|dialect| {
+                let translator = self.translator_for(&dialect)?;
+                Some(DialectInfo {
+                    provider: String::from(translator.name()),
+                    capabilities: translator.capabilities(),
+                    dialect,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc, time::Duration};
+
+    use crate::{
+        funtranslations_api::client::FunTranslationsApiClient,
+        libretranslate_api::client::LibreTranslateApiClient,
+        translation::{registry::TranslatorRegistry, translator::Translator},
+    };
+
+    fn translators() -> Vec<Arc<dyn Translator>> {
+        vec![
+            Arc::new(FunTranslationsApiClient::new(
+                String::from("http://localhost"),
+                Duration::from_millis(200),
+            )),
+            Arc::new(LibreTranslateApiClient::new(
+                String::from("http://localhost"),
+                Duration::from_millis(200),
+                vec![String::from("it"), String::from("pirate")],
+            )),
+        ]
+    }
+
+    #[test]
+    fn translator_for_prefers_configured_routes() {
+        let mut routes = HashMap::new();
+        routes.insert(String::from("pirate"), String::from("libretranslate"));
+        let registry = TranslatorRegistry::new(translators(), &routes).unwrap();
+
+        assert_eq!(
+            registry.translator_for("pirate").unwrap().name(),
+            "libretranslate"
+        );
+        assert_eq!(
+            registry.translator_for("yoda").unwrap().name(),
+            "funtranslations"
+        );
+        assert_eq!(
+            registry.translator_for("it").unwrap().name(),
+            "libretranslate"
+        );
+        assert!(registry.translator_for("klingon").is_none());
+    }
+
+    #[test]
+    fn new_fails_on_routes_to_unknown_providers() {
+        let mut routes = HashMap::new();
+        routes.insert(String::from("yoda"), String::from("babelfish"));
+
+        assert!(TranslatorRegistry::new(translators(), &routes).is_err());
+    }
+}
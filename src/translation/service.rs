@@ -0,0 +1,516 @@
+use std::{sync::Arc, time::Duration};
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::cache::{
+    disk::DiskNamespace,
+    entry::CacheEntry,
+    memory::TtlCache,
+    stale::{Refreshes, StalePolicy, Staleness},
+};
+use crate::translation::chain::{
+    is_retryable, ChainedTranslation, ProviderHealth, TranslatorChain,
+};
+use crate::translation::registry::DialectInfo;
+use crate::translation::translator::{QuotaStatus, Translation, TranslationError};
+
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ProviderQuota {
+    pub provider: String,
+    #[serde(flatten)]
+    pub quota: QuotaStatus,
+}
+
+/// Fronts the translation backends with a cache, so that the same text is
+/// only ever sent upstream once per dialect and ttl.
+pub struct TranslationService {
+    backend: CachedChain,
+    stale_policy: StalePolicy,
+    refreshes: Arc<Refreshes>,
+}
+
+/// The chain together with the caches its answers go to, cheap to clone
+/// into background refreshes.
+#[derive(Clone)]
+struct CachedChain {
+    chain: Arc<TranslatorChain>,
+    cache: Arc<TtlCache<Translation>>,
+    disk_cache: Option<Arc<DiskNamespace>>,
+}
+
+impl TranslationService {
+    pub fn new(chain: TranslatorChain, cache: TtlCache<Translation>) -> Self {
+        Self {
+            backend: CachedChain {
+                chain: Arc::new(chain),
+                cache: Arc::new(cache),
+                disk_cache: None,
+            },
+            stale_policy: StalePolicy::default(),
+            refreshes: Arc::new(Refreshes::default()),
+        }
+    }
+
+    /// Backs the in-memory cache with a persistent one, consulted on misses,
+    /// so that translations outlive restarts.
+    pub fn with_disk_cache(mut self, disk_cache: DiskNamespace) -> Self {
+        self.backend.disk_cache = Some(Arc::new(disk_cache));
+        self
+    }
+
+    /// Serves expired translations according to `stale_policy`. The cache
+    /// needs a grace period at least as long to still hold them.
+    pub fn with_stale_policy(mut self, stale_policy: StalePolicy) -> Self {
+        self.stale_policy = stale_policy;
+        self
+    }
+
+    /// Cache hits report zero attempts. Only answers from the preferred
+    /// provider are cached, so that it gets a chance again on the next
+    /// request after a failover.
+    pub async fn translate(
+        &self,
+        dialect: &str,
+        text: &str,
+    ) -> Result<ChainedTranslation, TranslationError> {
+        let key = cache_key(dialect, text);
+        let (translation, age) = match self.backend.cached(&key).await {
+            Some(cached) => cached,
+            None => return self.backend.translate(dialect, text).await,
+        };
+        let expired_for = match age.checked_sub(self.backend.cache.ttl()) {
+            None => return Ok(cache_hit(translation, Staleness::Fresh)),
+            Some(expired_for) => expired_for,
+        };
+        if expired_for < self.stale_policy.while_revalidate {
+            if self.refreshes.start(&key) {
+                let backend = self.backend.clone();
+                let refreshes = self.refreshes.clone();
+                let (dialect, text) = (String::from(dialect), String::from(text));
+                tokio::spawn(async move {
+                    let _ = backend.translate(&dialect, &text).await;
+                    refreshes.finish(&key);
+                });
+            }
+            return Ok(cache_hit(translation, Staleness::Stale));
+        }
+        if expired_for >= self.stale_policy.if_error {
+            return self.backend.translate(dialect, text).await;
+        }
+        // A fallback answering means the preferred provider failed: the
+        // cached translation, which came from it, is the better answer.
+        match self.backend.translate(dialect, text).await {
+            Ok(chained) if !chained.preferred => {
+                Ok(cache_hit(translation, Staleness::RevalidationFailed))
+            }
+            Err(error) if is_retryable(&error) => {
+                Ok(cache_hit(translation, Staleness::RevalidationFailed))
+            }
+            result => result,
+        }
+    }
+
+    /// Cached translations, in memory and on disk, keyed `<dialect>:<text>`.
+    pub async fn cache_entries(&self) -> Vec<CacheEntry> {
+        let mut entries = self.backend.cache.entries();
+        if let Some(disk_cache) = &self.backend.disk_cache {
+            entries.extend(disk_cache.entries().await.unwrap_or_default());
+        }
+        entries
+    }
+
+    pub async fn cached_entry(&self, key: &str) -> Option<Translation> {
+        self.backend
+            .cached(key)
+            .await
+            .map(|(translation, _)| translation)
+    }
+
+    /// The cached translation of `text` into `dialect`, if any, without
+    /// calling a provider.
+    pub async fn cached(&self, dialect: &str, text: &str) -> Option<Translation> {
+        self.cached_entry(&cache_key(dialect, text)).await
+    }
+
+    /// Whether a translation of `text` into `dialect` is cached, in memory or
+    /// on disk, including expired ones still kept for their grace period.
+    pub async fn is_cached(&self, dialect: &str, text: &str) -> bool {
+        self.backend
+            .cached(&cache_key(dialect, text))
+            .await
+            .is_some()
+    }
+
+    /// Drops the cached translations of `text`, into any dialect.
+    pub async fn purge_text(&self, text: &str) -> usize {
+        let text = String::from(text);
+        self.purge_matching(move |key| {
+            key.split_once(':').map(|(_, cached)| cached) == Some(text.as_str())
+        })
+        .await
+    }
+
+    pub async fn purge_dialect(&self, dialect: &str) -> usize {
+        let dialect = String::from(dialect);
+        self.purge_matching(move |key| {
+            key.split_once(':').map(|(cached, _)| cached) == Some(dialect.as_str())
+        })
+        .await
+    }
+
+    pub async fn purge_all(&self) -> usize {
+        self.purge_matching(|_| true).await
+    }
+
+    async fn purge_matching(
+        &self,
+        matches: impl Fn(&str) -> bool + Clone + Send + 'static,
+    ) -> usize {
+        let mut purged = self.backend.cache.remove_matching(&matches);
+        if let Some(disk_cache) = &self.backend.disk_cache {
+            purged += disk_cache
+                .remove_matching(matches)
+                .await
+                .unwrap_or_default();
+        }
+        purged
+    }
+
+    pub fn dialects(&self) -> Vec<DialectInfo> {
+        self.backend.chain.registry().dialects()
+    }
+
+    pub fn providers(&self) -> Vec<ProviderHealth> {
+        self.backend.chain.health()
+    }
+
+    /// Whether the preferred provider for `dialect` is healthy and can still
+    /// be called while leaving `reserve` calls of its budget, as the chain
+    /// accounts for it. Background jobs check this so as to leave nothing
+    /// but live traffic to the fallbacks.
+    pub fn has_quota_left(&self, dialect: &str, reserve: u32) -> bool {
+        let chain = &self.backend.chain;
+        chain
+            .candidates(dialect)
+            .first()
+            .is_none_or(|translator| chain.has_budget(translator.as_ref(), reserve))
+    }
+
+    /// How long until a rate limited provider can be called again, when
+    /// all of them have spent their budget.
+    pub fn retry_after(&self) -> Option<Duration> {
+        let quotas = self.quotas();
+        if quotas.is_empty() {
+            return None;
+        }
+        quotas
+            .iter()
+            .map(|provider| provider.quota.retry_after())
+            .collect::<Option<Vec<Duration>>>()?
+            .into_iter()
+            .min()
+    }
+
+    /// Budgets of the providers that track their own consumption.
+    pub fn quotas(&self) -> Vec<ProviderQuota> {
+        self.backend
+            .chain
+            .registry()
+            .translators()
+            .iter()
+            .filter_map(|translator| {
+                Some(ProviderQuota {
+                    provider: String::from(translator.name()),
+                    quota: translator.quota()?,
+                })
+            })
+            .collect()
+    }
+}
+
+impl CachedChain {
+    /// A translation from the memory cache, with its age, or else from the
+    /// disk cache, which only holds unexpired ones.
+    async fn cached(&self, key: &str) -> Option<(Translation, Duration)> {
+        if let Some(cached) = self.cache.get_with_age(key) {
+            return Some(cached);
+        }
+        let translation = self.disk_cache.as_ref()?.get::<Translation>(key).await?;
+        self.cache.insert(String::from(key), translation.clone());
+        Some((translation, Duration::ZERO))
+    }
+
+    async fn translate(
+        &self,
+        dialect: &str,
+        text: &str,
+    ) -> Result<ChainedTranslation, TranslationError> {
+        let chained = self.chain.translate(dialect, text).await?;
+        if chained.preferred {
+            let key = cache_key(dialect, text);
+            if let Some(disk_cache) = &self.disk_cache {
+                disk_cache.insert(&key, chained.translation.clone()).await;
+            }
+            self.cache.insert(key, chained.translation.clone());
+        }
+        Ok(chained)
+    }
+}
+
+fn cache_hit(translation: Translation, staleness: Staleness) -> ChainedTranslation {
+    ChainedTranslation {
+        translation,
+        attempts: 0,
+        preferred: true,
+        staleness,
+    }
+}
+
+fn cache_key(dialect: &str, text: &str) -> String {
+    format!("{}:{}", dialect, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, env, fs, sync::Arc, time::Duration};
+
+    use claim::{assert_err, assert_ok};
+    use serde_json::json;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use crate::{
+        cache::{
+            disk::DiskCache,
+            memory::TtlCache,
+            stale::{StalePolicy, Staleness},
+        },
+        funtranslations_api::client::FunTranslationsApiClient,
+        translation::{
+            chain::{HealthPolicy, TranslatorChain},
+            local::LocalTranslator,
+            registry::TranslatorRegistry,
+            service::TranslationService,
+            translator::{TranslationError, Translator},
+        },
+    };
+
+    fn chain(base_url: String) -> TranslatorChain {
+        let translators: Vec<Arc<dyn Translator>> = vec![
+            Arc::new(FunTranslationsApiClient::new(
+                base_url,
+                Duration::from_millis(200),
+            )),
+            Arc::new(LocalTranslator),
+        ];
+        TranslatorChain::new(
+            TranslatorRegistry::new(translators, &HashMap::new()).unwrap(),
+            vec![String::from("funtranslations"), String::from("local")],
+            HealthPolicy {
+                failure_threshold: 3,
+                cooldown: Duration::from_secs(60),
+            },
+            HashMap::new(),
+        )
+        .unwrap()
+    }
+
+    fn translation_service(base_url: String) -> TranslationService {
+        TranslationService::new(chain(base_url), TtlCache::new(Duration::from_secs(60), 10))
+    }
+
+    #[tokio::test]
+    async fn translate_serves_repeated_texts_from_the_cache() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let translation_service = translation_service(mock_server.uri());
+
+        let json_body = json!({
+            "success": {
+              "total": 1
+            },
+            "contents": {
+              "translated": "Lost a planet,  master obiwan has.",
+              "text": "Master Obiwan has lost a planet.",
+              "translation": "yoda"
+            }
+          }
+        );
+        Mock::given(path("/translate/yoda.json"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json_body))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let first = translation_service
+            .translate("yoda", "Master Obiwan has lost a planet.")
+            .await;
+        let second = translation_service
+            .translate("yoda", "Master Obiwan has lost a planet.")
+            .await;
+
+        assert_ok!(&first);
+        assert_ok!(&second);
+        let second = second.unwrap();
+        assert_eq!(second.attempts, 0);
+        assert_eq!(
+            second.translation.translated,
+            "Lost a planet,  master obiwan has."
+        );
+    }
+
+    #[tokio::test]
+    async fn translate_serves_texts_from_the_disk_cache_after_a_restart() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let cache_path = env::temp_dir().join(format!(
+            "yap-translations-{}-{}.sqlite",
+            std::process::id(),
+            rand::random::<u32>()
+        ));
+        let disk_cache = Arc::new(DiskCache::open(&cache_path).unwrap());
+
+        let json_body = json!({
+            "success": {
+              "total": 1
+            },
+            "contents": {
+              "translated": "Lost a planet,  master obiwan has.",
+              "text": "Master Obiwan has lost a planet.",
+              "translation": "yoda"
+            }
+          }
+        );
+        Mock::given(path("/translate/yoda.json"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json_body))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let before_restart = translation_service(mock_server.uri())
+            .with_disk_cache(disk_cache.namespace("translation", Duration::from_secs(60), 10))
+            .translate("yoda", "Master Obiwan has lost a planet.")
+            .await;
+        let after_restart = translation_service(mock_server.uri())
+            .with_disk_cache(disk_cache.namespace("translation", Duration::from_secs(60), 10))
+            .translate("yoda", "Master Obiwan has lost a planet.")
+            .await;
+        fs::remove_file(&cache_path).unwrap();
+
+        assert_ok!(&before_restart);
+        assert_ok!(&after_restart);
+        let after_restart = after_restart.unwrap();
+        assert_eq!(after_restart.attempts, 0);
+        assert_eq!(after_restart.translation.engine, "funtranslations");
+    }
+
+    #[tokio::test]
+    async fn translate_fails_for_dialects_no_backend_supports() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let translation_service = translation_service(mock_server.uri());
+
+        // Act
+        let translation = translation_service.translate("klingon", "Hello").await;
+
+        assert_err!(&translation);
+        assert_eq!(
+            translation.unwrap_err(),
+            TranslationError::UnsupportedDialect {
+                dialect: String::from("klingon")
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn translate_does_not_cache_failover_answers() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let translation_service = translation_service(mock_server.uri());
+
+        Mock::given(path("/translate/yoda.json"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(429))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let first = translation_service
+            .translate("yoda", "Master Obiwan has lost a planet.")
+            .await;
+        let second = translation_service
+            .translate("yoda", "Master Obiwan has lost a planet.")
+            .await;
+
+        assert_ok!(&first);
+        assert_ok!(&second);
+        let second = second.unwrap();
+        assert_eq!(second.attempts, 2);
+        assert_eq!(second.translation.engine, "local");
+        assert_eq!(
+            second.translation.translated,
+            "Lost a planet, master Obiwan has."
+        );
+    }
+
+    #[tokio::test]
+    async fn translate_serves_expired_entries_when_only_a_fallback_answers() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let stale_policy = StalePolicy {
+            while_revalidate: Duration::ZERO,
+            if_error: Duration::from_secs(60),
+        };
+        let translation_service = TranslationService::new(
+            chain(mock_server.uri()),
+            TtlCache::new(Duration::from_millis(20), 10)
+                .with_grace_period(stale_policy.grace_period()),
+        )
+        .with_stale_policy(stale_policy);
+        let json_body = json!({
+            "success": {"total": 1},
+            "contents": {
+              "translated": "Lost a planet,  master obiwan has.",
+              "text": "Master Obiwan has lost a planet.",
+              "translation": "yoda"
+            }
+        });
+        Mock::given(path("/translate/yoda.json"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json_body))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/translate/yoda.json"))
+            .and(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let _ = translation_service
+            .translate("yoda", "Master Obiwan has lost a planet.")
+            .await;
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        let revalidated = translation_service
+            .translate("yoda", "Master Obiwan has lost a planet.")
+            .await;
+
+        assert_ok!(&revalidated);
+        let revalidated = revalidated.unwrap();
+        assert_eq!(revalidated.staleness, Staleness::RevalidationFailed);
+        assert_eq!(revalidated.translation.engine, "funtranslations");
+        assert_eq!(
+            revalidated.translation.translated,
+            "Lost a planet,  master obiwan has."
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_graphql::SimpleObject;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema, SimpleObject)]
+pub struct Translation {
+    pub dialect: String,
+    pub original: String,
+    pub translated: String,
+    /// Name of the backend that produced the translated text.
+    pub engine: String,
+}
+
+/// What a backend can do, reported alongside its dialects so that callers
+/// can pick the right one without knowing the concrete type.
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct Capabilities {
+    /// Whether texts leave the process to be translated.
+    pub remote: bool,
+    /// Whether the backend enforces a request quota.
+    pub rate_limited: bool,
+    pub max_text_length: Option<usize>,
+}
+
+/// Remaining request budget of a rate limited backend.
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct QuotaStatus {
+    pub hourly_limit: u32,
+    pub hourly_remaining: u32,
+    pub daily_limit: u32,
+    pub daily_remaining: u32,
+}
+
+impl QuotaStatus {
+    /// How long until the next call fits in the budget, if it is spent: the
+    /// next UTC midnight once the daily budget is gone, otherwise the time
+    /// the hourly budget takes to earn one call back.
+    pub fn retry_after(&self) -> Option<Duration> {
+        if self.daily_remaining == 0 {
+            let since_midnight = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs() % SECONDS_PER_DAY);
+            Some(Duration::from_secs(SECONDS_PER_DAY - since_midnight))
+        } else if self.hourly_remaining == 0 {
+            Some(Duration::from_secs(
+                60 * 60 / u64::from(self.hourly_limit.max(1)),
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+#[async_trait]
+pub trait Translator: Send + Sync {
+    /// Name the backend is referred to by in the configuration.
+    fn name(&self) -> &str;
+
+    async fn translate(&self, dialect: &str, text: &str) -> Result<Translation, TranslationError>;
+
+    fn supported_dialects(&self) -> Vec<String>;
+
+    fn capabilities(&self) -> Capabilities;
+
+    /// Budget left, for backends tracking their own consumption.
+    fn quota(&self) -> Option<QuotaStatus> {
+        None
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TranslationError {
+    InternalError,
+    NotFound,
+    RateLimited,
+    QuotaExhausted,
+    Timeout,
+    /// No provider able to serve the dialect could be called.
+    Unavailable,
+    BadRequest {
+        message: String,
+    },
+    UnsupportedDialect {
+        dialect: String,
+    },
+}